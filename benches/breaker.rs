@@ -0,0 +1,140 @@
+#![feature(test)]
+extern crate test;
+extern crate vigenere;
+
+use test::Bencher;
+use vigenere::{breaker, cipher};
+use vigenere::language::Language;
+
+const WORDS: &'static [&'static str] = &["the", "of", "and", "to", "in", "a", "is", "that",
+                                         "for", "it", "as", "was", "with", "be", "by", "on",
+                                         "not", "he", "this", "are", "or", "his", "from", "at",
+                                         "which", "but", "have", "an", "had", "they", "you",
+                                         "were", "their", "one", "all", "we", "can", "her",
+                                         "has", "there", "been", "if", "more", "when", "will",
+                                         "would", "who", "so", "no"];
+
+/// English looking text of the given length, always the same for a length.
+fn corpus(len: usize) -> Vec<u8> {
+    let mut text = Vec::with_capacity(len + 16);
+    let mut state: u32 = 12345;
+    while text.len() < len {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        let word = WORDS[(state >> 16) as usize % WORDS.len()];
+        text.extend_from_slice(word.as_bytes());
+        text.push(if state % 13 == 0 { b'\n' } else { b' ' });
+    }
+    text.truncate(len);
+    text
+}
+
+fn encrypted(len: usize, key_len: usize) -> cipher::CipherText {
+    let key: Vec<u8> = (0..key_len).map(|i| (i * 37 + 11) as u8).collect();
+    let key = cipher::Key::from_vec(key).unwrap();
+    cipher::encrypt_in_place(cipher::PlainText::from(corpus(len)), &key)
+}
+
+fn bench_hamming_dist(b: &mut Bencher, len: usize) {
+    let text = corpus(2 * len);
+    let (bytes1, bytes2) = text.split_at(len);
+    b.bytes = len as u64;
+    b.iter(|| breaker::hamming_dist(bytes1, bytes2));
+}
+
+fn bench_guess_key_size(b: &mut Bencher, len: usize) {
+    let cipher = encrypted(len, 7);
+    b.iter(|| breaker::guess_key_size(&cipher, Language::English));
+}
+
+fn bench_decode_single_key(b: &mut Bencher, len: usize) {
+    let cipher = encrypted(len, 1);
+    b.bytes = len as u64;
+    b.iter(|| breaker::decode_single_key(&cipher));
+}
+
+fn bench_decode_text(b: &mut Bencher, len: usize, key_len: usize) {
+    let cipher = encrypted(len, key_len);
+    b.bytes = len as u64;
+    b.iter(|| breaker::decode_text(&cipher));
+}
+
+fn bench_decode_text_parallel(b: &mut Bencher, len: usize, key_len: usize) {
+    let cipher = encrypted(len, key_len);
+    let options = breaker::ParallelOptions::default();
+    b.bytes = len as u64;
+    b.iter(|| breaker::decode_text_parallel(&cipher, &options, &mut breaker::NoProgress));
+}
+
+#[bench]
+fn count_set_bits(b: &mut Bencher) {
+    b.iter(|| {
+        (0..256)
+            .map(|byte| breaker::count_set_bits(test::black_box(byte as u8)))
+            .sum::<i32>()
+    });
+}
+
+#[bench]
+fn hamming_dist_40(b: &mut Bencher) {
+    bench_hamming_dist(b, 40);
+}
+
+#[bench]
+fn hamming_dist_4k(b: &mut Bencher) {
+    bench_hamming_dist(b, 4096);
+}
+
+#[bench]
+fn hamming_dist_64k(b: &mut Bencher) {
+    bench_hamming_dist(b, 65536);
+}
+
+#[bench]
+fn guess_key_size_1k(b: &mut Bencher) {
+    bench_guess_key_size(b, 1024);
+}
+
+#[bench]
+fn guess_key_size_64k(b: &mut Bencher) {
+    bench_guess_key_size(b, 65536);
+}
+
+#[bench]
+fn decode_single_key_100(b: &mut Bencher) {
+    bench_decode_single_key(b, 100);
+}
+
+#[bench]
+fn decode_single_key_4k(b: &mut Bencher) {
+    bench_decode_single_key(b, 4096);
+}
+
+#[bench]
+fn decode_text_1k_key_3(b: &mut Bencher) {
+    bench_decode_text(b, 1024, 3);
+}
+
+#[bench]
+fn decode_text_16k_key_7(b: &mut Bencher) {
+    bench_decode_text(b, 16384, 7);
+}
+
+#[bench]
+fn decode_text_64k_key_16(b: &mut Bencher) {
+    bench_decode_text(b, 65536, 16);
+}
+
+#[bench]
+fn decode_text_parallel_1k_key_3(b: &mut Bencher) {
+    bench_decode_text_parallel(b, 1024, 3);
+}
+
+#[bench]
+fn decode_text_parallel_16k_key_7(b: &mut Bencher) {
+    bench_decode_text_parallel(b, 16384, 7);
+}
+
+#[bench]
+fn decode_text_parallel_64k_key_16(b: &mut Bencher) {
+    bench_decode_text_parallel(b, 65536, 16);
+}
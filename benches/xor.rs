@@ -0,0 +1,79 @@
+#![feature(test)]
+extern crate test;
+extern crate vigenere;
+
+use test::Bencher;
+use vigenere::cipher;
+
+const INPUT_LEN: usize = 1 << 20;
+
+fn bytes(len: usize, seed: usize) -> Vec<u8> {
+    (0..len).map(|i| ((i * 31 + seed) % 251) as u8).collect()
+}
+
+fn bench_xor(b: &mut Bencher, key_len: usize) {
+    let input = bytes(INPUT_LEN, 0);
+    let key = cipher::Key::from_vec(bytes(key_len, 7)).unwrap();
+    b.bytes = INPUT_LEN as u64;
+    b.iter(|| cipher::repeating_xor(&input, &key));
+}
+
+fn bench_xor_in_place(b: &mut Bencher, key_len: usize) {
+    let mut input = bytes(INPUT_LEN, 0);
+    let key = cipher::Key::from_vec(bytes(key_len, 7)).unwrap();
+    b.bytes = INPUT_LEN as u64;
+    b.iter(|| {
+        cipher::repeating_xor_in_place(&mut input, &key);
+        test::black_box(&input);
+    });
+}
+
+#[bench]
+fn xor_key_1(b: &mut Bencher) {
+    bench_xor(b, 1);
+}
+
+#[bench]
+fn xor_key_3(b: &mut Bencher) {
+    bench_xor(b, 3);
+}
+
+#[bench]
+fn xor_key_16(b: &mut Bencher) {
+    bench_xor(b, 16);
+}
+
+#[bench]
+fn xor_key_29(b: &mut Bencher) {
+    bench_xor(b, 29);
+}
+
+#[bench]
+fn xor_key_1000(b: &mut Bencher) {
+    bench_xor(b, 1000);
+}
+
+#[bench]
+fn xor_in_place_key_1(b: &mut Bencher) {
+    bench_xor_in_place(b, 1);
+}
+
+#[bench]
+fn xor_in_place_key_3(b: &mut Bencher) {
+    bench_xor_in_place(b, 3);
+}
+
+#[bench]
+fn xor_in_place_key_16(b: &mut Bencher) {
+    bench_xor_in_place(b, 16);
+}
+
+#[bench]
+fn xor_in_place_key_29(b: &mut Bencher) {
+    bench_xor_in_place(b, 29);
+}
+
+#[bench]
+fn xor_in_place_key_1000(b: &mut Bencher) {
+    bench_xor_in_place(b, 1000);
+}
@@ -0,0 +1,317 @@
+use super::breaker;
+use super::byte_matrix::ByteMatrix;
+use super::language::Language;
+use super::report::Coincidence;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Width of the longest bar of the histograms.
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// Index of coincidence of one period: of every column of the bytes, their
+/// average, and the average over the columns of the letters alone. `hamming` is
+/// the normalized Hamming distance `guess_key_size` ranks the key sizes by, when
+/// the text has the four blocks of the period it needs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeriodStatistics {
+    pub size: u32,
+    pub columns: Vec<f32>,
+    pub ioc: f32,
+    pub letter_ioc: f32,
+    pub hamming: Option<f32>,
+}
+
+/// Statistics of a text: byte and letter counts, index of coincidence,
+/// Shannon entropy, the Friedman estimate of the key length of a letter cipher
+/// of the language, the scores of every period from 1 to the maximum, and the
+/// autocorrelation of the bytes and the letters up to twice the maximum period
+/// with the periods its peaks point to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub len: usize,
+    pub byte_counts: Vec<u32>,
+    pub letter_counts: [u32; 26],
+    pub ioc: f32,
+    pub letter_ioc: f32,
+    pub entropy: f32,
+    pub friedman: Option<f32>,
+    pub periods: Vec<PeriodStatistics>,
+    pub coincidences: Vec<Coincidence>,
+    pub letter_coincidences: Vec<Coincidence>,
+    pub correlated_periods: Vec<u32>,
+    pub correlated_letter_periods: Vec<u32>,
+}
+
+impl Analysis {
+    /// Analyzes `bytes` for the periods 1 to `max_period`, or fewer if the text
+    /// doesn't have two bytes per column.
+    pub fn new(bytes: &[u8], max_period: usize, language: Language) -> Analysis {
+        let mut byte_counts = vec![0; 256];
+        for byte in bytes {
+            byte_counts[*byte as usize] += 1;
+        }
+        let letters = breaker::letters(bytes);
+        let mut letter_counts = [0; 26];
+        for letter in letters.iter() {
+            letter_counts[*letter as usize] += 1;
+        }
+        let max_period = max_period.min(bytes.len() / 2);
+        let letter_ioc = breaker::index_of_coincidence(&letters);
+        let coincidences = breaker::autocorrelation(bytes, 2 * max_period);
+        let letter_coincidences = breaker::autocorrelation(&letters, 2 * max_period);
+        Analysis {
+            len: bytes.len(),
+            byte_counts: byte_counts,
+            letter_counts: letter_counts,
+            ioc: breaker::index_of_coincidence(bytes),
+            letter_ioc: letter_ioc,
+            entropy: entropy(bytes),
+            friedman: breaker::friedman_letters(letter_ioc, language),
+            periods: (1..max_period + 1).map(|size| period(bytes, &letters, size)).collect(),
+            correlated_periods: breaker::autocorrelation_periods(&coincidences, max_period),
+            correlated_letter_periods: breaker::autocorrelation_periods(&letter_coincidences,
+                                                                        max_period),
+            coincidences: coincidences,
+            letter_coincidences: letter_coincidences,
+        }
+    }
+
+    /// Human readable report, with the histograms as bar charts.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Length: {} bytes, {} letters", self.len, self.letters());
+        let _ = writeln!(out, "Entropy: {:.4} bits per byte", self.entropy);
+        let _ = writeln!(out,
+                         "Index of coincidence: {:.4} bytes, {:.4} letters",
+                         self.ioc,
+                         self.letter_ioc);
+        match self.friedman {
+            Some(length) => {
+                let _ = writeln!(out, "Friedman key length: {:.2} letters\n", length);
+            }
+            None => {
+                let _ = writeln!(out, "Friedman key length: none, the letters look random\n");
+            }
+        }
+
+        let _ = writeln!(out, "Bytes:");
+        let counts: Vec<(String, u32)> = self.byte_counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, count)| *count > 0)
+            .map(|(byte, count)| (byte_label(byte as u8), *count))
+            .collect();
+        histogram(&mut out, &counts);
+
+        let _ = writeln!(out, "\nLetters:");
+        let counts: Vec<(String, u32)> = self.letter_counts
+            .iter()
+            .enumerate()
+            .map(|(letter, count)| (((b'a' + letter as u8) as char).to_string(), *count))
+            .collect();
+        histogram(&mut out, &counts);
+
+        let _ = writeln!(out, "\nperiod  hamming score     ioc  letter ioc  column ioc");
+        for period in self.periods.iter() {
+            let hamming = period.hamming.map_or(String::new(), |score| format!("{:.4}", score));
+            let columns: Vec<String> =
+                period.columns.iter().map(|ioc| format!("{:.3}", ioc)).collect();
+            let _ = writeln!(out,
+                             "{:>6}  {:>13}  {:>6.4}  {:>10.4}  {}",
+                             period.size,
+                             hamming,
+                             period.ioc,
+                             period.letter_ioc,
+                             columns.join(" "));
+        }
+
+        let _ = writeln!(out,
+                         "\nAutocorrelation peaks: {:?} bytes, {:?} letters\n",
+                         self.correlated_periods,
+                         self.correlated_letter_periods);
+        let _ = writeln!(out,
+                         "{:>6}  {:>12}  {:>6}  {:>12}  {:>6}",
+                         "offset",
+                         "coincidences",
+                         "rate",
+                         "letters",
+                         "rate");
+        for (i, coincidence) in self.coincidences.iter().enumerate() {
+            let letters = self.letter_coincidences
+                .get(i)
+                .map_or(String::new(), |letters| {
+                    format!("  {:>12}  {:>6.4}", letters.count, letters.rate)
+                });
+            let _ = writeln!(out,
+                             "{:>6}  {:>12}  {:>6.4}{}",
+                             coincidence.offset,
+                             coincidence.count,
+                             coincidence.rate,
+                             letters);
+        }
+        out
+    }
+
+    fn letters(&self) -> u32 {
+        self.letter_counts.iter().sum()
+    }
+}
+
+impl ToJson for PeriodStatistics {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("size".to_string(), self.size.to_json());
+        object.insert("columns".to_string(), self.columns.to_json());
+        object.insert("ioc".to_string(), self.ioc.to_json());
+        object.insert("letter_ioc".to_string(), self.letter_ioc.to_json());
+        object.insert("hamming".to_string(), self.hamming.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for Analysis {
+    fn to_json(&self) -> Json {
+        let mut bytes = BTreeMap::new();
+        for (byte, count) in self.byte_counts.iter().enumerate().filter(|&(_, count)| *count > 0) {
+            bytes.insert(format!("{:02x}", byte), count.to_json());
+        }
+        let mut letters = BTreeMap::new();
+        for (letter, count) in self.letter_counts.iter().enumerate() {
+            letters.insert(((b'a' + letter as u8) as char).to_string(), count.to_json());
+        }
+        let mut object = BTreeMap::new();
+        object.insert("len".to_string(), self.len.to_json());
+        object.insert("bytes".to_string(), Json::Object(bytes));
+        object.insert("letters".to_string(), Json::Object(letters));
+        object.insert("ioc".to_string(), self.ioc.to_json());
+        object.insert("letter_ioc".to_string(), self.letter_ioc.to_json());
+        object.insert("entropy".to_string(), self.entropy.to_json());
+        object.insert("friedman".to_string(), self.friedman.to_json());
+        object.insert("periods".to_string(), self.periods.to_json());
+        object.insert("coincidences".to_string(), self.coincidences.to_json());
+        object.insert("letter_coincidences".to_string(), self.letter_coincidences.to_json());
+        object.insert("correlated_periods".to_string(), self.correlated_periods.to_json());
+        object.insert("correlated_letter_periods".to_string(),
+                      self.correlated_letter_periods.to_json());
+        Json::Object(object)
+    }
+}
+
+/// Shannon entropy of the bytes, in bits per byte.
+pub fn entropy(bytes: &[u8]) -> f32 {
+    let mut counts = [0u32; 256];
+    for byte in bytes {
+        counts[*byte as usize] += 1;
+    }
+    let len = bytes.len() as f32;
+    counts.iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f32 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn period(bytes: &[u8], letters: &[u8], size: usize) -> PeriodStatistics {
+    let columns: Vec<f32> = ByteMatrix::to_matrix(bytes, size)
+        .into_rows()
+        .iter()
+        .map(|column| breaker::index_of_coincidence(column))
+        .collect();
+    let ioc = columns.iter().sum::<f32>() / size as f32;
+    let letter_ioc = if letters.len() >= 2 * size {
+        ByteMatrix::to_matrix(letters, size)
+            .into_rows()
+            .iter()
+            .map(|column| breaker::index_of_coincidence(column))
+            .sum::<f32>() / size as f32
+    } else {
+        0.0
+    };
+    PeriodStatistics {
+        size: size as u32,
+        columns: columns,
+        ioc: ioc,
+        letter_ioc: letter_ioc,
+        hamming: if bytes.len() >= 4 * size {
+            Some(breaker::calc_size_score(bytes, size as i32))
+        } else {
+            None
+        },
+    }
+}
+
+/// Writes a bar for every label, the longest one for the highest count.
+fn histogram(out: &mut String, counts: &[(String, u32)]) {
+    let max = counts.iter().map(|&(_, count)| count).max().unwrap_or(0);
+    for &(ref label, count) in counts {
+        let width = if max == 0 {
+            0
+        } else {
+            (count as usize * HISTOGRAM_WIDTH + max as usize / 2) / max as usize
+        };
+        let _ = writeln!(out, "{:>4} {:>7} {}", label, count, "#".repeat(width));
+    }
+}
+
+fn byte_label(byte: u8) -> String {
+    if byte > 0x20 && byte < 0x7f {
+        (byte as char).to_string()
+    } else {
+        format!("{:02x}", byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::{Json, ToJson};
+
+    #[test]
+    fn test_analysis() {
+        let analysis = Analysis::new(b"abababab", 3, Language::English);
+        assert_eq!(8, analysis.len);
+        assert_eq!(4, analysis.byte_counts[b'a' as usize]);
+        assert_eq!(4, analysis.letter_counts[1]);
+        assert_eq!(1.0, analysis.entropy);
+        assert!(analysis.friedman.is_some());
+        assert_eq!(3, analysis.periods.len());
+        assert_eq!(vec![1.0, 1.0], analysis.periods[1].columns);
+        assert_eq!(1.0, analysis.periods[1].letter_ioc);
+        assert_eq!(Some(0.0), analysis.periods[1].hamming);
+        assert_eq!(None, analysis.periods[2].hamming);
+        assert_eq!(6, analysis.coincidences.len());
+        assert_eq!(6, analysis.coincidences[1].count);
+        assert_eq!(2, Analysis::new(b"abcd", 10, Language::English).periods.len());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(0.0, entropy(b"aaaa"));
+        assert_eq!(1.0, entropy(b"abab"));
+        let bytes: Vec<u8> = (0..256).map(|byte| byte as u8).collect();
+        assert_eq!(8.0, entropy(&bytes));
+    }
+
+    #[test]
+    fn test_text() {
+        let text = Analysis::new(b"aab\n", 2, Language::English).to_text();
+        assert!(text.contains("   a       2 ##################################################\n"));
+        assert!(text.contains("   b       1 #########################\n"));
+        assert!(text.contains("  0a       1 #########################\n"));
+        assert!(text.contains("     2"));
+    }
+
+    #[test]
+    fn test_json() {
+        let json = Analysis::new(b"aab\n", 2, Language::English).to_json();
+        let parsed = Json::from_str(&json.to_string()).unwrap();
+        assert_eq!(Some(4), parsed.find("len").and_then(Json::as_u64));
+        assert_eq!(Some(2), parsed.find_path(&["bytes", "61"]).and_then(Json::as_u64));
+        assert_eq!(Some(1), parsed.find_path(&["letters", "b"]).and_then(Json::as_u64));
+        assert_eq!(2, parsed.find("periods").and_then(Json::as_array).unwrap().len());
+        assert_eq!(3, parsed.find("coincidences").and_then(Json::as_array).unwrap().len());
+    }
+}
@@ -0,0 +1,268 @@
+use super::cipher::{wipe, Error};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// What to do when the output file already exists.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WritePolicy {
+    Overwrite,
+    NoClobber,
+}
+
+/// Output file that is written to a temporary file in the same directory, and
+/// only takes the place of the destination when `commit` is called. Dropping it
+/// without committing removes the temporary file and leaves the destination
+/// untouched. Writes are buffered, and the buffer is wiped once it's written so
+/// no copy of the contents is left in memory.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: File,
+    buffer: Vec<u8>,
+    policy: WritePolicy,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P, policy: WritePolicy) -> Result<AtomicFile, Error> {
+        let path = path.as_ref().to_path_buf();
+        if policy == WritePolicy::NoClobber && path.exists() {
+            return Err(Error::OutputExists(path));
+        }
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => {
+                let reason = format!("{} is not a file name", path.display());
+                return Err(Error::Io(io::Error::new(ErrorKind::InvalidInput, reason)));
+            }
+        };
+
+        let mut attempt = 0;
+        loop {
+            let temp_path = dir.join(format!(".{}.{}-{}.tmp", name, process::id(), attempt));
+            match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+                Ok(file) => {
+                    return Ok(AtomicFile {
+                        path: path,
+                        temp_path: temp_path,
+                        file: file,
+                        buffer: Vec::with_capacity(BUFFER_SIZE),
+                        policy: policy,
+                        committed: false,
+                    })
+                }
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists && attempt < 100 => {
+                    attempt += 1;
+                }
+                Err(err) => return Err(Error::from(err)),
+            }
+        }
+    }
+
+    /// Flushes the temporary file to disk and moves it to the destination.
+    pub fn commit(mut self) -> Result<(), Error> {
+        try!(self.flush());
+        try!(self.file.sync_all());
+
+        match self.policy {
+            WritePolicy::Overwrite => {
+                // Keep the mode of the file being replaced, the temporary file
+                // was created with the default one.
+                if let Ok(metadata) = fs::metadata(&self.path) {
+                    try!(fs::set_permissions(&self.temp_path, metadata.permissions()));
+                }
+                try!(fs::rename(&self.temp_path, &self.path));
+            }
+            WritePolicy::NoClobber => {
+                // Linking fails if the destination appeared in the meantime,
+                // unlike rename that would silently replace it.
+                match fs::hard_link(&self.temp_path, &self.path) {
+                    Ok(_) => try!(fs::remove_file(&self.temp_path)),
+                    Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {
+                        return Err(Error::OutputExists(self.path.clone()));
+                    }
+                    Err(_) => {
+                        if self.path.exists() {
+                            return Err(Error::OutputExists(self.path.clone()));
+                        }
+                        try!(fs::rename(&self.temp_path, &self.path));
+                    }
+                }
+            }
+        }
+        self.committed = true;
+        Ok(())
+    }
+}
+
+const BUFFER_SIZE: usize = 8192;
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() + buf.len() > BUFFER_SIZE {
+            try!(self.flush());
+        }
+        if buf.len() >= BUFFER_SIZE {
+            self.file.write(buf)
+        } else {
+            self.buffer.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let written = self.file.write_all(&self.buffer);
+        wipe(&mut self.buffer);
+        try!(written);
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        wipe(&mut self.buffer);
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Fails with `Error::SameFile` when both paths lead to the same file, writing
+/// the output would destroy the input.
+pub fn check_distinct<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<(), Error> {
+    if same_file(input.as_ref(), output.as_ref()) {
+        Err(Error::SameFile(output.as_ref().to_path_buf()))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn same_file(path1: &Path, path2: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(path1), fs::metadata(path2)) {
+        (Ok(meta1), Ok(meta2)) => meta1.dev() == meta2.dev() && meta1.ino() == meta2.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(path1: &Path, path2: &Path) -> bool {
+    match (fs::canonicalize(path1), fs::canonicalize(path2)) {
+        (Ok(path1), Ok(path2)) => path1 == path2,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::cipher::Error;
+    use std::fs;
+    use std::slice;
+
+    #[test]
+    fn test_commit() {
+        let path = "./target/test-atomic-commit.txt";
+        fs::write(path, "old").unwrap();
+
+        let mut file = AtomicFile::create(path, WritePolicy::Overwrite).unwrap();
+        file.write_all("new".as_bytes()).unwrap();
+        assert_eq!("old", fs::read_to_string(path).unwrap());
+        file.commit().unwrap();
+        assert_eq!("new", fs::read_to_string(path).unwrap());
+        assert_eq!(0, temp_files("test-atomic-commit.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commit_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = "./target/test-atomic-permissions.txt";
+        fs::write(path, "old").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let mut file = AtomicFile::create(path, WritePolicy::Overwrite).unwrap();
+        file.write_all("new".as_bytes()).unwrap();
+        file.commit().unwrap();
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+    }
+
+    #[test]
+    fn test_buffer_wiped() {
+        let path = "./target/test-atomic-wiped.txt";
+        let mut file = AtomicFile::create(path, WritePolicy::Overwrite).unwrap();
+        file.write_all("secret".as_bytes()).unwrap();
+        assert_eq!("secret".as_bytes(), &file.buffer[..]);
+        file.flush().unwrap();
+        let memory = unsafe { slice::from_raw_parts(file.buffer.as_ptr(), BUFFER_SIZE) };
+        assert!(memory.iter().all(|b| *b == 0));
+
+        file.write_all(&[1; BUFFER_SIZE]).unwrap();
+        assert!(file.buffer.is_empty());
+        file.commit().unwrap();
+        assert_eq!(6 + BUFFER_SIZE as u64, fs::metadata(path).unwrap().len());
+    }
+
+    #[test]
+    fn test_drop_without_commit() {
+        let path = "./target/test-atomic-drop.txt";
+        fs::write(path, "old").unwrap();
+        {
+            let mut file = AtomicFile::create(path, WritePolicy::Overwrite).unwrap();
+            file.write_all("new".as_bytes()).unwrap();
+        }
+        assert_eq!("old", fs::read_to_string(path).unwrap());
+        assert_eq!(0, temp_files("test-atomic-drop.txt"));
+    }
+
+    #[test]
+    fn test_no_clobber() {
+        let path = "./target/test-atomic-no-clobber.txt";
+        fs::write(path, "old").unwrap();
+        match AtomicFile::create(path, WritePolicy::NoClobber) {
+            Err(Error::OutputExists(_)) => {}
+            _ => panic!("existing output must not be replaced"),
+        }
+
+        let new_path = "./target/test-atomic-no-clobber-new.txt";
+        let _ = fs::remove_file(new_path);
+        let file = AtomicFile::create(new_path, WritePolicy::NoClobber).unwrap();
+        fs::write(new_path, "raced").unwrap();
+        match file.commit() {
+            Err(Error::OutputExists(_)) => {}
+            _ => panic!("output created after the check must not be replaced"),
+        }
+        assert_eq!("raced", fs::read_to_string(new_path).unwrap());
+    }
+
+    #[test]
+    fn test_check_distinct() {
+        let path = "./target/test-atomic-same.txt";
+        fs::write(path, "text").unwrap();
+        match check_distinct(path, "./target/../target/test-atomic-same.txt") {
+            Err(Error::SameFile(_)) => {}
+            _ => panic!("same file not detected"),
+        }
+        assert!(check_distinct(path, "./target/test-atomic-other.txt").is_ok());
+    }
+
+    fn temp_files(name: &str) -> usize {
+        let prefix = format!(".{}.", name);
+        fs::read_dir("./target")
+            .unwrap()
+            .filter(|entry| {
+                entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(&prefix)
+            })
+            .count()
+    }
+}
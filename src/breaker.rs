@@ -1,13 +1,28 @@
-use super::cipher;
+use super::cipher::{self, read_word, WORD};
 use super::byte_matrix;
-use std::ops::BitAnd;
 use std::collections::BinaryHeap;
 use std::cmp::Ordering;
 use std::f32;
+use super::atomic_file::check_distinct;
+use super::pool::ThreadPool;
+use super::report::{BreakReport, Coincidence, ColumnKey, KeySizeScore};
+use super::language::{self, Language};
+use super::identify;
 use std::thread;
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::panic;
+use std::time::Instant;
+use std::cmp;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::str::FromStr;
 
-fn calc_size_score(cipher: &[u8], size: i32) -> f32 {
+/// Average Hamming distance between the first four blocks of `size` bytes,
+/// divided by the size. The cipher text needs at least `4 * size` bytes.
+pub fn calc_size_score(cipher: &[u8], size: i32) -> f32 {
 
     let b1 = &cipher[0..size as usize];
     let b2 = &cipher[size as usize..2 * size as usize];
@@ -46,74 +61,463 @@ impl PartialOrd for KeyScore {
     }
 }
 
+const MIN_DETECT_COLUMN: usize = 8;
+
+/// Fewest letters per column a period of the letter ciphers has to leave.
+const MIN_LETTER_COLUMN: usize = 10;
+
+/// Most period guesses tried on the letter ciphers.
+const LETTER_GUESSES: usize = 4;
+
+/// Fraction of the highest period index of coincidence a guess has to reach.
+const PERIOD_IOC_RATIO: f32 = 0.9;
+
+/// Least score of a period in `autocorrelation_periods`.
+const AUTOCORRELATION_PEAK: f32 = 4.0;
+
+/// Most periods `autocorrelation_periods` returns.
+const AUTOCORRELATION_GUESSES: usize = 2;
+
+/// Period guesses the hill climbing tries, it's slower than the column solvers.
+const CLIMB_GUESSES: usize = 2;
+
+/// Rounds of moving every column to its best offset.
+const ALIGN_PASSES: usize = 2;
+
+/// Times the plain alphabet is climbed from a random one for every period.
+const CLIMB_RESTARTS: usize = 4;
+
+/// Plain alphabet swaps tried in every climb.
+const CLIMB_STEPS: usize = 20_000;
+
+/// Worst drop in letter pair log probability a swap can take early in a climb.
+const CLIMB_TEMPERATURE: f32 = 0.03;
+
+/// Plain alphabet swaps tried with the offsets refitted, at the end of a climb.
+const POLISH_STEPS: usize = 10_000;
+
+/// As `CLIMB_TEMPERATURE` for the final swaps, lower since they start close.
+const POLISH_TEMPERATURE: f32 = 0.01;
+
+/// Seed of the random alphabets, so the same text always breaks the same way.
+const CLIMB_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Fitness gain a longer period needs to be chosen over a shorter one.
+const CLIMB_PERIOD_MARGIN: f32 = 0.1;
+
+/// Shortest cipher text, key sizes are scored comparing 4 blocks of it.
+const MIN_BREAK_LEN: usize = 4;
+
+/// Observer for long running breaks. `started` receives the key size guesses that
+/// will be tried, the other methods are called as columns and key sizes are
+/// solved. Every method does nothing by default.
+pub trait Progress {
+    fn started(&mut self, _key_sizes: &[u32]) {}
+
+    fn column_solved(&mut self, _key_size: u32, _column: usize, _columns: usize) {}
+
+    fn key_size_evaluated(&mut self, _key_size: u32, _confidence: f32) {}
+
+    fn best_score(&mut self, _key_size: u32, _confidence: f32) {}
+}
+
+/// `Progress` that ignores every update.
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+/// Cipher the text is broken as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CipherKind {
+    /// Repeating XOR over bytes, the cipher text is base64 encoded.
+    Xor,
+    /// Alphabetic Vigenère, the cipher text is read as is.
+    Vigenere,
+    /// Quagmire I, the cipher text is read as is.
+    Quagmire,
+    /// Vigenère with a key of digits, the cipher text is read as is.
+    Gronsfeld,
+    /// Porta, the cipher text is read as is.
+    Porta,
+    /// Trithemius progressive shift, the cipher text is read as is.
+    Trithemius,
+    /// Whatever `identify::identify` finds most likely, the cipher text is read
+    /// as is and base64 decoded if it turns out to be XOR.
+    Auto,
+}
+
+impl CipherKind {
+    /// Whether the cipher text is read and written base64 encoded.
+    pub fn is_binary(&self) -> bool {
+        *self == CipherKind::Xor
+    }
+}
+
+impl FromStr for CipherKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<CipherKind, String> {
+        match name {
+            "xor" => Ok(CipherKind::Xor),
+            "vigenere" => Ok(CipherKind::Vigenere),
+            "quagmire1" => Ok(CipherKind::Quagmire),
+            "gronsfeld" => Ok(CipherKind::Gronsfeld),
+            "porta" => Ok(CipherKind::Porta),
+            "trithemius" => Ok(CipherKind::Trithemius),
+            "auto" => Ok(CipherKind::Auto),
+            _ => Err(format!("Unknown cipher {}", name)),
+        }
+    }
+}
+
+/// Settings for `decode_text_parallel` and the `break_*` functions. `threads` is
+/// the size of the worker pool, and when `threshold` is set the search stops as
+/// soon as a candidate reaches that confidence (the fraction of scored bytes that
+/// look like text). Both are only used by the XOR attack. `cipher` picks the
+/// attack, and `language` is the language of the plain text the letter ciphers
+/// are compared against.
+#[derive(Clone, Debug)]
+pub struct ParallelOptions {
+    pub threads: usize,
+    pub threshold: Option<f32>,
+    pub cipher: CipherKind,
+    pub language: Language,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> ParallelOptions {
+        ParallelOptions {
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            threshold: None,
+            cipher: CipherKind::Xor,
+            language: Language::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Detection {
+    pub line: usize,
+    pub key_size: u32,
+    pub plain: cipher::PlainText,
+    pub confidence: f32,
+}
+
 pub fn break_file(input_path: &str, output_path: &str) -> Result<(), cipher::Error> {
-    let cipher = try!(cipher::CipherText::from_file(input_path));
-    let plain = try!(decode_text_parallel(cipher));
-    try!(plain.to_file(output_path));
+    try!(break_file_with(input_path,
+                         output_path,
+                         &ParallelOptions::default(),
+                         &mut NoProgress));
     Ok(())
 }
 
-pub fn decode_text_parallel(cipher: cipher::CipherText) -> Result<cipher::PlainText, cipher::Error> {
+/// Breaks the cipher text in `input_path` into `output_path`, and returns the
+/// report explaining how the key was chosen.
+pub fn break_file_with(input_path: &str,
+                       output_path: &str,
+                       options: &ParallelOptions,
+                       progress: &mut dyn Progress)
+                       -> Result<BreakReport, cipher::Error> {
+    try!(check_distinct(input_path, output_path));
+    let cipher = try!(read_cipher(try!(File::open(input_path)), options.cipher));
+    let (plain, report) = try!(decode_with(&cipher, options, progress));
+    try!(plain.to_file(output_path));
+    Ok(report)
+}
 
-    let mut best_score = 0;
-    let mut candidate = None;
-    let key_size_guesses = guess_key_size(&cipher);
+/// Same as `break_file_with`, reading the cipher text from `input` and writing
+/// the plain text to `output`.
+pub fn break_stream<R: Read, W: Write>(input: R,
+                                       output: W,
+                                       options: &ParallelOptions,
+                                       progress: &mut dyn Progress)
+                                       -> Result<BreakReport, cipher::Error> {
+    let cipher = try!(read_cipher(input, options.cipher));
+    let (plain, report) = try!(decode_with(&cipher, options, progress));
+    try!(plain.to_writer(output));
+    Ok(report)
+}
+
+fn read_cipher<R: Read>(input: R, kind: CipherKind) -> Result<cipher::CipherText, cipher::Error> {
+    if kind.is_binary() {
+        cipher::CipherText::from_reader(input)
+    } else {
+        cipher::CipherText::from_text_reader(input)
+    }
+}
+
+/// Breaks the cipher text with the attack for `options.cipher`.
+pub fn decode_with(cipher: &cipher::CipherText,
+                   options: &ParallelOptions,
+                   progress: &mut dyn Progress)
+                   -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    match options.cipher {
+        CipherKind::Xor => decode_text_report(cipher, options, progress),
+        CipherKind::Vigenere => decode_vigenere(cipher, options.language, progress),
+        CipherKind::Quagmire => decode_quagmire(cipher, options.language, progress),
+        CipherKind::Gronsfeld => decode_gronsfeld(cipher, options.language, progress),
+        CipherKind::Porta => decode_porta(cipher, options.language, progress),
+        CipherKind::Trithemius => decode_trithemius(cipher, options.language, progress),
+        CipherKind::Auto => decode_auto(cipher, options, progress),
+    }
+}
+
+/// Breaks the cipher text with the attack for the cipher type
+/// `identify::identify` ranks first, or fails when that type has no attack.
+fn decode_auto(cipher: &cipher::CipherText,
+               options: &ParallelOptions,
+               progress: &mut dyn Progress)
+               -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    let hypotheses = identify::identify(cipher, options.language);
+    let best = try!(hypotheses.first().ok_or(cipher::Error::NoCandidate));
+    let kind = try!(best.cipher.attack().ok_or_else(|| {
+        cipher::Error::Failure(format!("The text looks like {}, which can't be broken",
+                                       best.cipher))
+    }));
+    let options = ParallelOptions { cipher: kind, ..options.clone() };
+    if kind.is_binary() {
+        let bytes = try!(identify::as_binary(cipher).ok_or(cipher::Error::NoCandidate));
+        decode_with(&bytes, &options, progress)
+    } else {
+        decode_with(cipher, &options, progress)
+    }
+}
+
+/// Same as `decode_text`, but the columns of every key size guess are decoded
+/// on a pool of `options.threads` workers. A panicking worker is reported as an
+/// `Error::Failure` instead of bringing down the caller. Updates are reported to
+/// `progress` from the calling thread.
+pub fn decode_text_parallel(cipher: &cipher::CipherText,
+                            options: &ParallelOptions,
+                            progress: &mut dyn Progress)
+                            -> Result<cipher::PlainText, cipher::Error> {
+    decode_text_report(cipher, options, progress).map(|(plain, _)| plain)
+}
+
+/// Key of a column along with its decrypted bytes, as a worker sends it back.
+type SolvedColumn = (ColumnKey, Vec<u8>);
+
+/// `decode_text_parallel` that also returns a `BreakReport` with the key size
+/// scores, the key chosen for every column and the time it took.
+pub fn decode_text_report(cipher: &cipher::CipherText,
+                          options: &ParallelOptions,
+                          progress: &mut dyn Progress)
+                          -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
 
+    let start = Instant::now();
+    try!(check_length(cipher));
+    let key_size_guesses = guess_key_size(cipher, options.language);
+    progress.started(&key_size_guesses);
+    let bytes: &[u8] = cipher;
+    let pool = ThreadPool::new(options.threads);
+    let cancelled = Arc::new(AtomicBool::new(false));
     let (tx, rx) = mpsc::channel();
 
-    for key_size in key_size_guesses.iter() {
-        let tx = tx.clone();
-        let cipher = cipher.clone();
-        let key_size = *key_size;
-        thread::spawn(move || {
-            let plain = break_cipher(&cipher, key_size);
-            let score = score(&plain.as_bytes());
-            tx.send((plain, score)).unwrap();
+    // Every column of every key size guess is an independent job, so the pool
+    // stays busy even when there are fewer guesses than threads.
+    let mut columns: Vec<Vec<Option<SolvedColumn>>> = Vec::new();
+    let mut jobs = 0;
+    for (guess, key_size) in key_size_guesses.iter().enumerate() {
+        let rows = byte_matrix::ByteMatrix::to_matrix(bytes, *key_size as usize).into_rows();
+        columns.push(vec![None; rows.len()]);
+        for (column, row) in rows.into_iter().enumerate() {
+            let tx = tx.clone();
+            let cancelled = cancelled.clone();
+            jobs += 1;
+            pool.execute(move || {
+                if cancelled.load(AtomicOrdering::SeqCst) {
+                    return;
+                }
+                let result = panic::catch_unwind(move || {
+                        let mut row = row;
+                        let key = solve_single_key(&row);
+                        xor_byte(&mut row, key.key);
+                        (key, row)
+                    })
+                    .map_err(|_| cipher::Error::Failure("Worker thread panicked".to_string()));
+                let _ = tx.send((guess, column, result));
+            });
+        }
+    }
+    drop(tx);
+
+    let mut best: Option<(f32, usize, cipher::PlainText, Vec<ColumnKey>)> = None;
+    for _ in 0..jobs {
+        let (guess, column, result) = match rx.recv() {
+            Ok(message) => message,
+            Err(_) => {
+                return Err(cipher::Error::Failure("Worker thread stopped".to_string()));
+            }
+        };
+        let decoded = match result {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                cancelled.store(true, AtomicOrdering::SeqCst);
+                return Err(err);
+            }
+        };
+
+        let key_size = key_size_guesses[guess];
+        columns[guess][column] = Some(decoded);
+        progress.column_solved(key_size, column, key_size as usize);
+        if columns[guess].iter().any(Option::is_none) {
+            continue;
+        }
+        let (keys, rows): (Vec<ColumnKey>, Vec<Vec<u8>>) =
+            columns[guess].iter_mut().map(|row| row.take().unwrap()).unzip();
+        let plain = cipher::PlainText::from(byte_matrix::ByteMatrix::from_rows(rows).reassemble());
+        let confidence = confidence(&plain);
+        let keys = reduce_key(keys);
+        progress.key_size_evaluated(key_size, confidence);
+
+        // Ties go to the shorter key, a multiple of the key size decrypts the
+        // same, and then to the earlier guess, same as the sequential decode_text
+        let better = best.as_ref().map_or(confidence > 0.0, |best| {
+            let (best_confidence, best_guess, _, ref best_keys) = *best;
+            confidence > best_confidence ||
+            (confidence == best_confidence &&
+             (keys.len(), guess) < (best_keys.len(), best_guess))
         });
+        if better {
+            progress.best_score(keys.len() as u32, confidence);
+            best = Some((confidence, guess, plain, keys));
+        }
+        if options.threshold.is_some_and(|threshold| confidence >= threshold) {
+            cancelled.store(true, AtomicOrdering::SeqCst);
+            break;
+        }
+    }
+
+    match best {
+        None => Err(cipher::Error::NoCandidate),
+        Some((confidence, _, plain, keys)) => {
+            let report = BreakReport {
+                key_sizes: key_size_table(bytes, &key_size_guesses),
+                key_size: keys.len() as u32,
+                columns: keys,
+                confidence: confidence,
+                elapsed: start.elapsed(),
+            };
+            Ok((plain, report))
+        }
     }
+}
 
+/// Reads a file with one hex encoded cipher text per line, and ranks the lines by
+/// how plausible their best single or multi byte XOR decryption is. Empty lines
+/// and lines that aren't hex are skipped, `line` in the results is the 1-based
+/// line number in the file.
+pub fn detect_file(input_path: &str) -> Result<Vec<Detection>, cipher::Error> {
+    let file = try!(File::open(input_path));
+    detect_reader(file)
+}
 
-    for _key_size in key_size_guesses.iter() {
-        let (plain, score) = rx.recv().unwrap();
-        if score > best_score {
-            best_score = score;
-            candidate = Some(plain);
+/// Same as `detect_file`, reading the hex lines from `input`.
+pub fn detect_reader<R: Read>(input: R) -> Result<Vec<Detection>, cipher::Error> {
+    let mut line_numbers = Vec::new();
+    let mut ciphers = Vec::new();
+    for (i, line) in BufReader::new(input).lines().enumerate() {
+        let line = try!(line);
+        let hex = line.trim();
+        if hex.is_empty() {
+            continue;
+        }
+        if let Ok(cipher) = cipher::CipherText::from_hex(hex) {
+            ciphers.push(cipher);
+            line_numbers.push(i + 1);
         }
     }
 
-    match candidate {
-        None => Err(cipher::Error::Failure("Couldn't decode text".to_string())),
-        Some(plain) => Ok(plain),
+    let mut detections = detect_xor(&ciphers);
+    for detection in detections.iter_mut() {
+        detection.line = line_numbers[detection.line];
     }
+    Ok(detections)
 }
 
+/// Breaks every cipher text trying a single byte key and the guessed key sizes,
+/// and returns the best decryption of each one, most confident first. `line` in
+/// the results is the index of the cipher text in the input slice.
+///
+/// Short texts are easy to overfit with a long key, so only key sizes leaving at
+/// least `MIN_DETECT_COLUMN` bytes per column are tried, and the smallest key
+/// size wins on ties.
+pub fn detect_xor(ciphers: &[cipher::CipherText]) -> Vec<Detection> {
+    let mut detections: Vec<Detection> = ciphers.iter()
+        .enumerate()
+        .filter(|&(_, cipher)| !cipher.is_empty())
+        .map(|(i, cipher)| {
+            let len = cipher.len();
+            let mut key_sizes: Vec<u32> = guess_key_size(cipher, Language::default())
+                .into_iter()
+                .filter(|size| len / *size as usize >= MIN_DETECT_COLUMN)
+                .collect();
+            if !key_sizes.contains(&1) {
+                key_sizes.push(1);
+            }
+            key_sizes.sort();
 
-pub fn decode_text(cipher: &cipher::CipherText) -> Result<cipher::PlainText, cipher::Error> {
+            let mut best: Option<Detection> = None;
+            for key_size in key_sizes {
+                let plain = break_cipher(cipher, key_size);
+                let confidence = confidence(&plain);
+                if best.as_ref().is_none_or(|b| confidence > b.confidence) {
+                    best = Some(Detection {
+                        line: i,
+                        key_size: key_size,
+                        plain: plain,
+                        confidence: confidence,
+                    });
+                }
+            }
+            best.unwrap()
+        })
+        .collect();
 
+    detections.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(Ordering::Equal));
+    detections
+}
+
+pub fn decode_text(cipher: &cipher::CipherText) -> Result<cipher::PlainText, cipher::Error> {
+    try!(check_length(cipher));
     let mut best_score = 0;
+    let mut best_period = 0;
     let mut candidate = None;
-    let key_size_guesses = guess_key_size(cipher);
+    let key_size_guesses = guess_key_size(cipher, Language::default());
     for key_size in key_size_guesses.iter() {
-        let plain = break_cipher(cipher, *key_size);
-        let score = score(&plain.as_bytes());
-        if score > best_score {
+        let (plain, keys) = break_columns(cipher, *key_size);
+        let score = score(&plain);
+        let period = reduce_key(keys).len();
+        if score > best_score || (score == best_score && score > 0 && period < best_period) {
             best_score = score;
+            best_period = period;
             candidate = Some(plain);
         }
 
     }
     match candidate {
-        None => Err(cipher::Error::Failure("Couldn't decode text".to_string())),
+        None => Err(cipher::Error::NoCandidate),
         Some(plain) => Ok(plain),
     }
 }
 
+fn check_length(cipher: &[u8]) -> Result<(), cipher::Error> {
+    if cipher.len() < MIN_BREAK_LEN {
+        Err(cipher::Error::TooShort {
+            len: cipher.len(),
+            min: MIN_BREAK_LEN,
+        })
+    } else {
+        Ok(())
+    }
+}
 
-pub fn guess_key_size(cipher: &cipher::CipherText) -> Vec<u32> {
+pub fn guess_key_size(cipher: &cipher::CipherText, language: Language) -> Vec<u32> {
     let mut heap = BinaryHeap::new();
     let mut best = Vec::new();
-    for i in 1..40 {
-        let score = calc_size_score(&cipher.as_bytes(), i);
+    let max_size = cmp::min(40, cipher.len() / MIN_BREAK_LEN + 1);
+    for i in 1..max_size {
+        let score = calc_size_score(cipher, i as i32);
         heap.push(KeyScore {
             size: i as u32,
             score: score,
@@ -129,59 +533,169 @@ pub fn guess_key_size(cipher: &cipher::CipherText) -> Vec<u32> {
             count += 1;
         }
     }
+
+    // The Hamming distance often misses the key size on short keys, the
+    // autocorrelation peaks are tried as well
+    let coincidences = autocorrelation(cipher, 2 * max_size);
+    for size in autocorrelation_periods(&coincidences, max_size - 1) {
+        if !best.contains(&size) {
+            best.push(size);
+        }
+    }
+
+    // The Friedman estimate from the coincidence of the whole text is cheap
+    // and doesn't depend on how the blocks line up
+    if let Some(length) = friedman_bytes(index_of_coincidence(cipher), language) {
+        let size = length.round() as u32;
+        if (size as usize) < max_size && !best.contains(&size) {
+            best.push(size);
+        }
+    }
     best
 }
 
-pub fn hamming_dist(bytes1: &[u8], bytes2: &[u8]) -> i32 {
-    let mut count = 0;
-    for (i, val1) in bytes1.iter().enumerate() {
-        let diff_bytes = val1 ^ bytes2[i];
-        count += count_set_bits(diff_bytes);
+/// Hamming and index of coincidence scores of every key size `guess_key_size`
+/// looks at, flagging the ones in `guesses`.
+pub fn key_size_table(cipher: &[u8], guesses: &[u32]) -> Vec<KeySizeScore> {
+    let max_size = cmp::min(40, cipher.len() / MIN_BREAK_LEN + 1);
+    (1..max_size).map(|size| key_size_score(cipher, size, guesses)).collect()
+}
+
+fn key_size_score(cipher: &[u8], size: usize, guesses: &[u32]) -> KeySizeScore {
+    let rows = byte_matrix::ByteMatrix::to_matrix(cipher, size).into_rows();
+    let ioc = rows.iter().map(|row| index_of_coincidence(row)).sum::<f32>() / size as f32;
+    KeySizeScore {
+        size: size as u32,
+        hamming: calc_size_score(cipher, size as i32),
+        ioc: ioc,
+        guessed: guesses.contains(&(size as u32)),
     }
-    count
 }
 
+/// Probability that two bytes picked at random from the input are equal.
+pub fn index_of_coincidence(input: &[u8]) -> f32 {
+    if input.len() < 2 {
+        return 0.0;
+    }
+    let mut counts = [0u64; 256];
+    for byte in input {
+        counts[*byte as usize] += 1;
+    }
+    let pairs: u64 = counts.iter().map(|n| n * n.saturating_sub(1)).sum();
+    let len = input.len() as u64;
+    pairs as f32 / (len * (len - 1)) as f32
+}
+
+/// Number of bits that differ between the inputs, compared 8 bytes at a time.
+/// The shorter input is padded with zeros, so every set bit in the extra bytes
+/// of the longer one counts as a difference.
+pub fn hamming_dist(bytes1: &[u8], bytes2: &[u8]) -> i32 {
+    let (short, long) = if bytes1.len() <= bytes2.len() {
+        (bytes1, bytes2)
+    } else {
+        (bytes2, bytes1)
+    };
+    let (long, extra) = long.split_at(short.len());
 
-pub fn count_set_bits(mut byte: u8) -> i32 {
     let mut count = 0;
-    while byte != 0 {
-        byte = byte.bitand(byte - 1);
-        count += 1;
+    let mut words1 = short.chunks(WORD);
+    let mut words2 = long.chunks(WORD);
+    while let (Some(word1), Some(word2)) = (words1.next(), words2.next()) {
+        count += (read_word(word1) ^ read_word(word2)).count_ones();
+    }
+    for word in extra.chunks(WORD) {
+        count += read_word(word).count_ones();
     }
-    count
+    count as i32
+}
+
+pub fn count_set_bits(byte: u8) -> i32 {
+    byte.count_ones() as i32
 }
 
 pub fn break_cipher(cipher: &cipher::CipherText, key_size: u32) -> cipher::PlainText {
-    let matrix = byte_matrix::ByteMatrix::to_matrix(&cipher.as_bytes(), key_size as usize);
-    let matrix = matrix.transform(|vec: &Vec<u8>| {
-        let cipher = cipher::CipherText::new(vec);
-        let plain = decode_single_key(&cipher);
-        plain.as_bytes()
-    });
-    let decoded = matrix.reassemble();
-    let plain = cipher::PlainText::new(&decoded);
-    plain
+    break_columns(cipher, key_size).0
+}
+
+/// `break_cipher` that also returns the key found for every column.
+fn break_columns(cipher: &cipher::CipherText,
+                 key_size: u32)
+                 -> (cipher::PlainText, Vec<ColumnKey>) {
+    let (keys, rows): (Vec<ColumnKey>, Vec<Vec<u8>>) =
+        byte_matrix::ByteMatrix::to_matrix(cipher, key_size as usize)
+            .into_rows()
+            .into_iter()
+            .map(|mut column| {
+                let key = solve_single_key(&column);
+                xor_byte(&mut column, key.key);
+                (key, column)
+            })
+            .unzip();
+    (cipher::PlainText::from(byte_matrix::ByteMatrix::from_rows(rows).reassemble()), keys)
+}
+
+/// Length of the shortest key that repeated gives `key`, "ace" for "aceace".
+pub fn fundamental_period(key: &[u8]) -> usize {
+    (1..key.len())
+        .find(|period| {
+            key.len().is_multiple_of(*period) &&
+            key.iter().enumerate().all(|(i, byte)| *byte == key[i % period])
+        })
+        .unwrap_or(key.len())
+}
+
+/// The column keys of the shortest key that repeated gives the same key, a
+/// multiple of the key size recovers the key repeated.
+pub fn reduce_key(mut keys: Vec<ColumnKey>) -> Vec<ColumnKey> {
+    let bytes: Vec<u8> = keys.iter().map(|key| key.key).collect();
+    keys.truncate(fundamental_period(&bytes));
+    keys
 }
 
 pub fn decode_single_key(cipher: &cipher::CipherText) -> cipher::PlainText {
-    let mut plain = None;
-    let mut best_score = 0;
-    for key in 0...255_u8 {
-        let candidate = cipher::decrypt_single_key(cipher, key).unwrap();
-        let score = score(&candidate.as_bytes());
-        if score > best_score {
-            best_score = score;
-            plain = Some(candidate);
+    let key = solve_single_key(cipher);
+    cipher::decrypt_single_key(cipher, key.key).unwrap()
+}
+
+/// Tries every single byte key, and returns the best scoring one along with the
+/// runner-up. The candidates are scored without decrypting them into a buffer.
+pub fn solve_single_key(cipher: &[u8]) -> ColumnKey {
+    let mut best = ColumnKey {
+        key: 0,
+        score: 0,
+        runner_up: 0,
+        runner_up_score: 0,
+    };
+    for key in 0..=255_u8 {
+        let score = score_bytes(cipher.iter().map(|b| b ^ key));
+        if score > best.score {
+            best.runner_up = best.key;
+            best.runner_up_score = best.score;
+            best.key = key;
+            best.score = score;
+        } else if score > best.runner_up_score {
+            best.runner_up = key;
+            best.runner_up_score = score;
         }
     }
-    plain.unwrap()
+    best
+}
+
+fn xor_byte(bytes: &mut [u8], key: u8) {
+    for byte in bytes.iter_mut() {
+        *byte ^= key;
+    }
 }
 
 fn score(input: &[u8]) -> u32 {
-    input.iter().take(100).fold(0, |acc, b| {
-        if *b >= 97 && *b <= 122 {
+    score_bytes(input.iter().cloned())
+}
+
+fn score_bytes<I: Iterator<Item = u8>>(input: I) -> u32 {
+    input.take(100).fold(0, |acc, b| {
+        if (97..=122).contains(&b) {
             acc + 1
-        } else if *b >= 33 && *b <= 64 && acc > 0 {
+        } else if (33..=64).contains(&b) && acc > 0 {
             acc - 1
         } else {
             acc
@@ -189,11 +703,780 @@ fn score(input: &[u8]) -> u32 {
     })
 }
 
+/// Score of the text normalized by the number of bytes `score` looks at, so
+/// texts of different lengths can be compared.
+fn confidence(input: &[u8]) -> f32 {
+    let scored = cmp::min(input.len(), 100);
+    if scored == 0 {
+        0.0
+    } else {
+        score(input) as f32 / scored as f32
+    }
+}
+
+/// Breaks an alphabetic Vigenère cipher. Only the letters are analyzed: the key
+/// lengths whose columns have an index of coincidence closer to the language than
+/// to random text are tried, and the shift of every column is the one with the
+/// lowest chi-squared against the language letter frequencies. The plain text
+/// keeps the case and everything that isn't a letter.
+///
+/// The column scores in the report are the chi-squared statistics, so lower is
+/// better, and the confidence is `letter_confidence` of the plain text.
+pub fn decode_vigenere(cipher: &cipher::CipherText,
+                       language: Language,
+                       progress: &mut dyn Progress)
+                       -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    decode_periodic(cipher, language, progress, solve_shift, cipher::decrypt_vigenere)
+}
+
+/// Breaks a Gronsfeld cipher as `decode_vigenere`, trying only the shifts of
+/// the digits.
+pub fn decode_gronsfeld(cipher: &cipher::CipherText,
+                        language: Language,
+                        progress: &mut dyn Progress)
+                        -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    decode_periodic(cipher, language, progress, solve_digit, cipher::decrypt_gronsfeld)
+}
+
+/// Breaks a Porta cipher as `decode_vigenere`, trying the 13 key pairs on every
+/// column. The key found has the first letter of every pair.
+pub fn decode_porta(cipher: &cipher::CipherText,
+                    language: Language,
+                    progress: &mut dyn Progress)
+                    -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    decode_periodic(cipher, language, progress, solve_porta, cipher::decrypt_porta)
+}
+
+/// Breaks a cipher that uses a key letter per column, as `decode_vigenere`.
+/// `solve` finds the key of a column of letters from 0 to 25, and `decrypt`
+/// decrypts the whole text with the key.
+fn decode_periodic<S, D>(cipher: &cipher::CipherText,
+                         language: Language,
+                         progress: &mut dyn Progress,
+                         solve: S,
+                         decrypt: D)
+                         -> Result<(cipher::PlainText, BreakReport), cipher::Error>
+    where S: Fn(&[u8], &[f32; 26]) -> ColumnKey,
+          D: Fn(&cipher::CipherText, &cipher::Key) -> Result<cipher::PlainText, cipher::Error>
+{
+    let start = Instant::now();
+    let cipher_letters = letters(cipher);
+    let table = period_table(&cipher_letters);
+    if table.is_empty() {
+        return Err(cipher::Error::TooShort {
+            len: cipher_letters.len(),
+            min: MIN_LETTER_COLUMN,
+        });
+    }
+    let guesses = guess_periods(&cipher_letters, &table, language);
+    progress.started(&guesses);
+
+    let frequencies = language.letter_frequencies();
+    let mut best: Option<(f32, u32, cipher::PlainText, Vec<ColumnKey>)> = None;
+    for period in guesses.iter().cloned() {
+        let rows = byte_matrix::ByteMatrix::to_matrix(&cipher_letters, period as usize)
+            .into_rows();
+        let mut keys = Vec::with_capacity(rows.len());
+        for (column, row) in rows.iter().enumerate() {
+            keys.push(solve(row, &frequencies));
+            progress.column_solved(period, column, period as usize);
+        }
+        let key = try!(cipher::Key::from_vec(keys.iter().map(|key| key.key).collect()));
+        let plain = try!(decrypt(cipher, &key));
+        let confidence = letter_confidence(&letters(&plain), language);
+        let keys = reduce_key(keys);
+        progress.key_size_evaluated(period, confidence);
+
+        // Multiples of the key length decrypt the same, so they never replace it
+        if best.as_ref().is_none_or(|best| confidence > best.0) {
+            progress.best_score(keys.len() as u32, confidence);
+            best = Some((confidence, keys.len() as u32, plain, keys));
+        }
+    }
+
+    let (confidence, period, plain, keys) = best.unwrap();
+    let report = BreakReport {
+        key_sizes: table.into_iter()
+            .map(|mut score| {
+                score.guessed = guesses.contains(&score.size);
+                score
+            })
+            .collect(),
+        key_size: period,
+        columns: keys,
+        confidence: confidence,
+        elapsed: start.elapsed(),
+    };
+    Ok((plain, report))
+}
+
+/// Breaks a Trithemius cipher trying every start shift, the one whose plain
+/// letters have the lowest chi-squared against the language wins. The report
+/// has a single column with the start as a letter, `a` being no shift.
+pub fn decode_trithemius(cipher: &cipher::CipherText,
+                         language: Language,
+                         progress: &mut dyn Progress)
+                         -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    let start = Instant::now();
+    let cipher_letters = letters(cipher);
+    if cipher_letters.len() < MIN_LETTER_COLUMN {
+        return Err(cipher::Error::TooShort {
+            len: cipher_letters.len(),
+            min: MIN_LETTER_COLUMN,
+        });
+    }
+    progress.started(&[1]);
+    let frequencies = language.letter_frequencies();
+    let column = column_key((0..26)
+        .map(|shift| {
+            let mut counts = [0; 26];
+            for (i, letter) in cipher_letters.iter().enumerate() {
+                counts[(*letter as usize + 26 - (shift + i) % 26) % 26] += 1;
+            }
+            (chi_squared(&counts, &frequencies), b'a' + shift as u8)
+        })
+        .collect());
+    progress.column_solved(1, 0, 1);
+
+    let plain = cipher::decrypt_trithemius(cipher, column.key - b'a');
+    let confidence = letter_confidence(&letters(&plain), language);
+    progress.key_size_evaluated(1, confidence);
+    progress.best_score(1, confidence);
+    let report = BreakReport {
+        key_sizes: Vec::new(),
+        key_size: 1,
+        columns: vec![column],
+        confidence: confidence,
+        elapsed: start.elapsed(),
+    };
+    Ok((plain, report))
+}
+
+/// Breaks Quagmire I by lining up the columns and hill climbing the plain alphabet.
+pub fn decode_quagmire(cipher: &cipher::CipherText,
+                       language: Language,
+                       progress: &mut dyn Progress)
+                       -> Result<(cipher::PlainText, BreakReport), cipher::Error> {
+    let start = Instant::now();
+    let cipher_letters = letters(cipher);
+    let table = period_table(&cipher_letters);
+    if table.is_empty() {
+        return Err(cipher::Error::TooShort {
+            len: cipher_letters.len(),
+            min: MIN_LETTER_COLUMN,
+        });
+    }
+    let guesses: Vec<u32> = guess_periods(&cipher_letters, &table, language)
+        .into_iter()
+        .take(CLIMB_GUESSES)
+        .collect();
+    progress.started(&guesses);
+
+    let mut rng = XorShift(CLIMB_SEED);
+    let mut best: Option<(f32, Climber, ClimbState)> = None;
+    for period in guesses.iter().cloned() {
+        let climber = Climber::new(&cipher_letters, period as usize, language);
+        let mut aligned = ClimbState::new(period as usize);
+        climber.align(&mut aligned);
+        let mut period_best: Option<(f32, ClimbState)> = None;
+        for _ in 0..CLIMB_RESTARTS {
+            let mut state = aligned.clone();
+            rng.shuffle(&mut state.plain);
+            climber.climb(&mut state, &mut rng);
+            let fitness = climber.polish(&mut state, &mut rng);
+            if period_best.as_ref().is_none_or(|best| fitness > best.0) {
+                period_best = Some((fitness, state));
+            }
+        }
+        for column in 0..period as usize {
+            progress.column_solved(period, column, period as usize);
+        }
+
+        let (fitness, state) = period_best.unwrap();
+        let plain = climber.decrypt(&state);
+        progress.key_size_evaluated(period, letter_confidence(&plain, language));
+        if best.as_ref().is_none_or(|best| fitness > best.0 + CLIMB_PERIOD_MARGIN) {
+            let key_size = reduce_key(climber.column_keys(&state)).len() as u32;
+            progress.best_score(key_size, letter_confidence(&plain, language));
+            best = Some((fitness, climber, state));
+        }
+    }
+
+    let (_, climber, state) = best.unwrap();
+    let columns = reduce_key(climber.column_keys(&state));
+    let key = try!(cipher::Key::from_vec(columns.iter().map(|column| column.key).collect()));
+    let alphabets = try!(cipher::Alphabets::new(state.plain, straight_alphabet()));
+    let plain = try!(cipher::decrypt_quagmire(cipher, &alphabets, &key));
+    let report = BreakReport {
+        key_sizes: table.into_iter()
+            .map(|mut score| {
+                score.guessed = guesses.contains(&score.size);
+                score
+            })
+            .collect(),
+        key_size: columns.len() as u32,
+        columns: columns,
+        confidence: letter_confidence(&letters(&plain), language),
+        elapsed: start.elapsed(),
+    };
+    Ok((plain, report))
+}
+
+/// Xorshift random numbers, enough to shuffle alphabets and reproducible.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Random number from 0 to `n` - 1.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Random number from 0 to 1.
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn shuffle(&mut self, alphabet: &mut [u8; 26]) {
+        for i in (1..26).rev() {
+            let j = self.below(i + 1);
+            alphabet.swap(i, j);
+        }
+    }
+}
+
+/// Plain alphabet and column offsets the hill climbing is at, as in
+/// `cipher::decrypt_quagmire` with the straight cipher alphabet of Quagmire I.
+#[derive(Clone)]
+struct ClimbState {
+    plain: [u8; 26],
+    offsets: Vec<u8>,
+}
+
+impl ClimbState {
+    fn new(period: usize) -> ClimbState {
+        ClimbState {
+            plain: straight_alphabet(),
+            offsets: vec![0; period],
+        }
+    }
+}
+
+fn straight_alphabet() -> [u8; 26] {
+    cipher::keyed_alphabet("").unwrap()
+}
+
+/// Cipher letters of one period guess, with the language statistics the hill
+/// climbing scores them against.
+struct Climber {
+    letters: Vec<u8>,
+    period: usize,
+    counts: Vec<[u32; 26]>,
+    frequencies: [f32; 26],
+    log_frequencies: [f32; 26],
+    bigrams: Vec<f32>,
+}
+
+impl Climber {
+    fn new(letters: &[u8], period: usize, language: Language) -> Climber {
+        let rows = byte_matrix::ByteMatrix::to_matrix(letters, period).into_rows();
+        let frequencies = language.letter_frequencies();
+        let mut log_frequencies = [0.0; 26];
+        for (log, frequency) in log_frequencies.iter_mut().zip(frequencies.iter()) {
+            *log = frequency.ln();
+        }
+        Climber {
+            letters: letters.to_vec(),
+            period: period,
+            counts: rows.iter().map(|row| letter_counts(row)).collect(),
+            frequencies: frequencies,
+            log_frequencies: log_frequencies,
+            bigrams: language.bigram_log_probabilities(),
+        }
+    }
+
+    /// Sets the offsets that line the columns up, the ones giving the columns
+    /// merged the highest index of coincidence, and returns that index.
+    fn align(&self, state: &mut ClimbState) -> f32 {
+        // Repeated so the offsets don't need a modulo
+        let positions: Vec<[u32; 52]> = self.counts
+            .iter()
+            .map(|counts| {
+                let mut position = [0; 52];
+                position[..26].copy_from_slice(counts);
+                position[26..].copy_from_slice(counts);
+                position
+            })
+            .collect();
+        align_offsets(&positions, &mut state.offsets)
+    }
+
+    /// Climbs the plain alphabet from `state` for `CLIMB_STEPS` swaps, keeping
+    /// the offsets. Leaves it at the best point found and returns its fitness.
+    fn climb(&self, state: &mut ClimbState, rng: &mut XorShift) -> f32 {
+        let mut substitutions = vec![[0; 26]; self.period];
+        let mut current = self.fitness(state, &mut substitutions);
+        let mut best = (current, state.plain);
+        for step in 0..CLIMB_STEPS {
+            let temperature = CLIMB_TEMPERATURE * (1.0 - step as f32 / CLIMB_STEPS as f32);
+            let (i, j) = (rng.below(26), rng.below(26));
+            state.plain.swap(i, j);
+            let fitness = self.fitness(state, &mut substitutions);
+            if fitness > current || rng.unit() < ((fitness - current) / temperature).exp() {
+                current = fitness;
+                if fitness > best.0 {
+                    best = (fitness, state.plain);
+                }
+            } else {
+                state.plain.swap(i, j);
+            }
+        }
+        state.plain = best.1;
+        best.0
+    }
+
+    /// Climbs the plain alphabet from `state` for `POLISH_STEPS` swaps as in
+    /// `climb`, with the offsets fitted after each one. Leaves it at the best
+    /// point found and returns its fitness.
+    fn polish(&self, state: &mut ClimbState, rng: &mut XorShift) -> f32 {
+        let mut substitutions = vec![[0; 26]; self.period];
+        let mut best = (self.fitness(state, &mut substitutions), state.clone());
+        self.fit_offsets(state);
+        let mut current = self.fitness(state, &mut substitutions);
+        let mut offsets = state.offsets.clone();
+        for step in 0..POLISH_STEPS {
+            let temperature = POLISH_TEMPERATURE * (1.0 - step as f32 / POLISH_STEPS as f32);
+            let (i, j) = (rng.below(26), rng.below(26));
+            offsets.copy_from_slice(&state.offsets);
+            state.plain.swap(i, j);
+            self.fit_offsets(state);
+            let fitness = self.fitness(state, &mut substitutions);
+            if fitness > current || rng.unit() < ((fitness - current) / temperature).exp() {
+                current = fitness;
+                if fitness > best.0 {
+                    best = (fitness, state.clone());
+                }
+            } else {
+                state.plain.swap(i, j);
+                state.offsets.copy_from_slice(&offsets);
+            }
+        }
+        *state = best.1;
+        best.0
+    }
+
+    /// Sets the offset of every column to the one whose plain letters are the
+    /// most likely in the language.
+    fn fit_offsets(&self, state: &mut ClimbState) {
+        // Log frequency of the plain letter at every position of the alphabet,
+        // repeated so the offsets don't need a modulo.
+        let mut log_frequencies = [0.0; 52];
+        for (i, letter) in state.plain.iter().enumerate() {
+            log_frequencies[i] = self.log_frequencies[*letter as usize];
+            log_frequencies[i + 26] = log_frequencies[i];
+        }
+        for (column, counts) in self.counts.iter().enumerate() {
+            let mut best = (f32::MIN, 0);
+            for offset in 0..26 {
+                // The cipher letter `letter` decrypts to the plain letter at
+                // `letter - offset`
+                let shifted = &log_frequencies[26 - offset..52 - offset];
+                let likelihood: f32 =
+                    counts.iter().zip(shifted.iter()).map(|(count, log)| *count as f32 * log).sum();
+                if likelihood > best.0 {
+                    best = (likelihood, offset as u8);
+                }
+            }
+            state.offsets[column] = best.1;
+        }
+    }
+
+    /// Fills in the plain letter of every cipher letter, for every column.
+    fn substitute(&self, state: &ClimbState, substitutions: &mut [[u8; 26]]) {
+        for (substitution, offset) in substitutions.iter_mut().zip(state.offsets.iter()) {
+            for (letter, plain) in substitution.iter_mut().enumerate() {
+                *plain = state.plain[(letter + 26 - *offset as usize) % 26];
+            }
+        }
+    }
+
+    /// Plain letters of the state, skipping everything that isn't a letter.
+    fn decrypt(&self, state: &ClimbState) -> Vec<u8> {
+        let mut substitutions = vec![[0; 26]; self.period];
+        self.substitute(state, &mut substitutions);
+        self.letters
+            .iter()
+            .enumerate()
+            .map(|(i, letter)| substitutions[i % self.period][*letter as usize])
+            .collect()
+    }
+
+    /// Average log probability of the letter pairs of the plain text.
+    fn fitness(&self, state: &ClimbState, substitutions: &mut [[u8; 26]]) -> f32 {
+        self.substitute(state, substitutions);
+        let mut total = 0.0;
+        let mut column = 0;
+        let mut previous = substitutions[0][self.letters[0] as usize] as usize;
+        for letter in self.letters[1..].iter() {
+            column = if column + 1 == self.period { 0 } else { column + 1 };
+            let plain = substitutions[column][*letter as usize] as usize;
+            total += self.bigrams[previous * 26 + plain];
+            previous = plain;
+        }
+        total / cmp::max(self.letters.len() - 1, 1) as f32
+    }
+
+    /// Chi-squared of the plain letters of a column with every offset, best first.
+    fn offset_scores(&self, state: &ClimbState, column: usize) -> Vec<(f32, u8)> {
+        let mut scores: Vec<(f32, u8)> = (0..26)
+            .map(|offset| {
+                let mut plain_counts = [0; 26];
+                for (letter, count) in self.counts[column].iter().enumerate() {
+                    let index = (letter + 26 - offset as usize) % 26;
+                    plain_counts[state.plain[index] as usize] += *count;
+                }
+                (chi_squared(&plain_counts, &self.frequencies), offset)
+            })
+            .collect();
+        scores.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        scores
+    }
+
+    /// Key letter of every column, the one below the plain `a` at its offset,
+    /// with the best scoring other offset as the runner-up.
+    fn column_keys(&self, state: &ClimbState) -> Vec<ColumnKey> {
+        let plain_index = cipher::invert_alphabet(&state.plain);
+        let key_letter = |offset: u8| b'a' + (offset + plain_index[0]) % 26;
+        (0..self.period)
+            .map(|column| {
+                let offset = state.offsets[column];
+                let scores = self.offset_scores(state, column);
+                let score = scores.iter().find(|score| score.1 == offset).unwrap();
+                let runner_up = scores.iter().find(|score| score.1 != offset).unwrap();
+                ColumnKey {
+                    key: key_letter(offset),
+                    score: score.0.round() as u32,
+                    runner_up: key_letter(runner_up.1),
+                    runner_up_score: runner_up.0.round() as u32,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Moves every column, in turn, to the offset that matches best the other
+/// columns merged, and returns the index of coincidence of all of them merged.
+/// `positions` are the column counts as in `Climber::align`.
+fn align_offsets(positions: &[[u32; 52]], offsets: &mut [u8]) -> f32 {
+    let mut merged = [0; 26];
+    for (position, offset) in positions.iter().zip(offsets.iter()) {
+        for (count, column) in merged.iter_mut().zip(position[*offset as usize..].iter()) {
+            *count += *column;
+        }
+    }
+    for _ in 0..ALIGN_PASSES {
+        for (position, offset) in positions.iter().zip(offsets.iter_mut()) {
+            for (count, column) in merged.iter_mut().zip(position[*offset as usize..].iter()) {
+                *count -= *column;
+            }
+            let mut best = (0, 0);
+            for candidate in 0..26 {
+                let matches: u32 = merged.iter()
+                    .zip(position[candidate..].iter())
+                    .map(|(count, column)| count * column)
+                    .sum();
+                if matches > best.0 {
+                    best = (matches, candidate);
+                }
+            }
+            *offset = best.1 as u8;
+            for (count, column) in merged.iter_mut().zip(position[*offset as usize..].iter()) {
+                *count += *column;
+            }
+        }
+    }
+    let total: u32 = merged.iter().sum();
+    let pairs: u32 = merged.iter().map(|count| count * count.saturating_sub(1)).sum();
+    pairs as f32 / cmp::max(total * total.saturating_sub(1), 1) as f32
+}
+
+/// Letters of the text from 0 to 25, skipping everything else.
+pub fn letters(text: &[u8]) -> Vec<u8> {
+    text.iter().filter_map(|byte| cipher::letter_index(*byte)).collect()
+}
+
+/// Scores of the key lengths worth trying on the letters, the ones leaving at
+/// least `MIN_LETTER_COLUMN` letters per column.
+pub fn period_table(letters: &[u8]) -> Vec<KeySizeScore> {
+    let max_period = cmp::min(40, letters.len() / MIN_LETTER_COLUMN);
+    (1..max_period + 1).map(|period| key_size_score(letters, period, &[])).collect()
+}
+
+/// The shortest key lengths whose index of coincidence is closer to the language
+/// than to random letters and not far from the highest one, or the ones with the
+/// highest index when there are none. The Friedman estimate of the key length
+/// and the autocorrelation peaks of the letters are tried too, first when no
+/// length stands out.
+pub fn guess_periods(letters: &[u8], table: &[KeySizeScore], language: Language) -> Vec<u32> {
+    let best = table.iter().map(|score| score.ioc).fold(0.0, f32::max);
+    let threshold = (language.letter_ioc() + language::RANDOM_LETTER_IOC) / 2.0;
+    let max_period = table.iter().map(|score| score.size).max().unwrap_or(0) as usize;
+    let friedman = friedman_letters(index_of_coincidence(letters), language)
+        .map(|length| length.round() as u32);
+    let correlated = autocorrelation_periods(&autocorrelation(letters, 2 * max_period),
+                                             max_period);
+    let mut estimates = Vec::new();
+    for period in friedman.into_iter().chain(correlated) {
+        if table.iter().any(|score| score.size == period) && !estimates.contains(&period) {
+            estimates.push(period);
+        }
+    }
+
+    let mut guesses: Vec<u32> = table.iter()
+        .filter(|score| score.ioc >= threshold && score.ioc >= best * PERIOD_IOC_RATIO)
+        .map(|score| score.size)
+        .take(LETTER_GUESSES)
+        .collect();
+    if guesses.is_empty() {
+        let mut sorted = table.to_vec();
+        sorted.sort_by(|a, b| b.ioc.partial_cmp(&a.ioc).unwrap_or(Ordering::Equal));
+        guesses = estimates;
+        for score in sorted.iter() {
+            if guesses.len() >= LETTER_GUESSES {
+                break;
+            }
+            if !guesses.contains(&score.size) {
+                guesses.push(score.size);
+            }
+        }
+    } else {
+        for period in estimates {
+            if !guesses.contains(&period) {
+                guesses.push(period);
+            }
+        }
+    }
+    guesses
+}
+
+/// Coincidences of the text with itself shifted by every offset from 1 to
+/// `max_offset`, or as many as leave something to compare.
+pub fn autocorrelation(text: &[u8], max_offset: usize) -> Vec<Coincidence> {
+    (1..cmp::min(max_offset + 1, text.len()))
+        .map(|offset| {
+            let count = text.iter().zip(text[offset..].iter()).filter(|&(a, b)| a == b).count();
+            Coincidence {
+                offset: offset as u32,
+                count: count as u32,
+                rate: count as f32 / (text.len() - offset) as f32,
+            }
+        })
+        .collect()
+}
+
+/// Periods up to `max_period` whose multiples have the most coincidences, best
+/// first. Shifting a periodic cipher text by a multiple of the key length lines
+/// up bytes encrypted with the same key, so they coincide as often as the plain
+/// text does, and far more than at other offsets. Every period is scored with
+/// how many standard deviations the average rate at its multiples is above the
+/// average of all the offsets, weighted by the square root of the number of
+/// multiples, so the multiples of the key length score lower than it. Periods
+/// scoring below `AUTOCORRELATION_PEAK` aren't returned.
+pub fn autocorrelation_periods(table: &[Coincidence], max_period: usize) -> Vec<u32> {
+    if table.len() < 2 {
+        return Vec::new();
+    }
+    let len = table.len() as f32;
+    let average = table.iter().map(|coincidence| coincidence.rate).sum::<f32>() / len;
+    let deviation = (table.iter()
+        .map(|coincidence| (coincidence.rate - average) * (coincidence.rate - average))
+        .sum::<f32>() / len)
+        .sqrt();
+    if deviation == 0.0 {
+        return Vec::new();
+    }
+    let mut scores: Vec<(u32, f32)> = (2..cmp::min(max_period, table.len() / 2) + 1)
+        .map(|period| {
+            let multiples: Vec<f32> = table.iter()
+                .filter(|coincidence| (coincidence.offset as usize).is_multiple_of(period))
+                .map(|coincidence| coincidence.rate)
+                .collect();
+            let count = multiples.len() as f32;
+            let mean = multiples.iter().sum::<f32>() / count;
+            (period as u32, (mean - average) / deviation * count.sqrt())
+        })
+        .filter(|&(_, score)| score >= AUTOCORRELATION_PEAK)
+        .collect();
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    scores.iter().map(|&(period, _)| period).take(AUTOCORRELATION_GUESSES).collect()
+}
+
+/// Friedman estimate of the key length of a periodic cipher, `(κp - κr) / (κ -
+/// κr)`, from the index of coincidence `ioc` of the cipher text, `plain_ioc` of
+/// the plain text and `random_ioc` of random text. `None` when the cipher text
+/// isn't above random.
+pub fn friedman(ioc: f32, plain_ioc: f32, random_ioc: f32) -> Option<f32> {
+    if ioc > random_ioc {
+        Some(((plain_ioc - random_ioc) / (ioc - random_ioc)).max(1.0))
+    } else {
+        None
+    }
+}
+
+/// `friedman` for a letter cipher of the language, given the index of
+/// coincidence of the cipher text letters.
+pub fn friedman_letters(ioc: f32, language: Language) -> Option<f32> {
+    friedman(ioc, language.letter_ioc(), language::RANDOM_LETTER_IOC)
+}
+
+/// `friedman` for a byte cipher of text in the language, given the index of
+/// coincidence of the cipher text bytes.
+pub fn friedman_bytes(ioc: f32, language: Language) -> Option<f32> {
+    friedman(ioc, language.byte_ioc(), language::RANDOM_BYTE_IOC)
+}
+
+/// Shift of a column of letters whose decryption has the lowest chi-squared
+/// against the letter frequencies, along with the runner-up shift.
+fn solve_shift(column: &[u8], frequencies: &[f32; 26]) -> ColumnKey {
+    let counts = letter_counts(column);
+    column_key((0..26)
+                   .map(|shift| {
+                       let decrypted = map_counts(&counts, |letter| (letter + 26 - shift) % 26);
+                       (chi_squared(&decrypted, frequencies), b'a' + shift)
+                   })
+                   .collect())
+}
+
+/// As `solve_shift`, with the shifts of the digits of a Gronsfeld key.
+fn solve_digit(column: &[u8], frequencies: &[f32; 26]) -> ColumnKey {
+    let counts = letter_counts(column);
+    column_key((0..10)
+                   .map(|shift| {
+                       let decrypted = map_counts(&counts, |letter| (letter + 26 - shift) % 26);
+                       (chi_squared(&decrypted, frequencies), b'0' + shift)
+                   })
+                   .collect())
+}
+
+/// As `solve_shift`, with the 13 Porta key pairs.
+fn solve_porta(column: &[u8], frequencies: &[f32; 26]) -> ColumnKey {
+    let counts = letter_counts(column);
+    column_key((0..13)
+                   .map(|pair| {
+                       let decrypted = map_counts(&counts,
+                                                  |letter| cipher::porta_letter(letter, pair));
+                       (chi_squared(&decrypted, frequencies), b'a' + 2 * pair)
+                   })
+                   .collect())
+}
+
+/// Counts of the letters after replacing every letter with `map(letter)`.
+fn map_counts<F: Fn(u8) -> u8>(counts: &[u32; 26], map: F) -> [u32; 26] {
+    let mut mapped = [0; 26];
+    for (letter, count) in counts.iter().enumerate() {
+        mapped[map(letter as u8) as usize] += *count;
+    }
+    mapped
+}
+
+/// Column key with the lowest chi-squared of `scores`, and the runner-up.
+fn column_key(mut scores: Vec<(f32, u8)>) -> ColumnKey {
+    scores.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    ColumnKey {
+        key: scores[0].1,
+        score: scores[0].0.round() as u32,
+        runner_up: scores[1].1,
+        runner_up_score: scores[1].0.round() as u32,
+    }
+}
+
+fn letter_counts(letters: &[u8]) -> [u32; 26] {
+    let mut counts = [0; 26];
+    for letter in letters {
+        counts[*letter as usize] += 1;
+    }
+    counts
+}
+
+/// Chi-squared statistic of the letter counts against the expected frequencies,
+/// the lower the closer they are.
+pub fn chi_squared(counts: &[u32; 26], frequencies: &[f32; 26]) -> f32 {
+    let total: u32 = counts.iter().sum();
+    counts.iter()
+        .zip(frequencies.iter())
+        .map(|(count, frequency)| {
+            let expected = frequency * total as f32;
+            let difference = *count as f32 - expected;
+            difference * difference / expected
+        })
+        .sum()
+}
+
+/// How much the letter distribution looks like the language, from 0 to 1: the
+/// correlation of its frequencies with the language, relative to the correlation
+/// of the language with itself. Random letters score around 0.6.
+pub fn letter_confidence(letters: &[u8], language: Language) -> f32 {
+    if letters.is_empty() {
+        return 0.0;
+    }
+    let frequencies = language.letter_frequencies();
+    let counts = letter_counts(letters);
+    let correlation: f32 = counts.iter()
+        .zip(frequencies.iter())
+        .map(|(count, frequency)| *count as f32 * frequency)
+        .sum();
+    (correlation / letters.len() as f32 / language.letter_ioc()).min(1.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::super::cipher;
 
+    const QUIJOTE: &str =
+        "En un lugar de la Mancha, de cuyo nombre no quiero acordarme, no ha \
+         mucho tiempo que vivía un hidalgo de los de lanza en astillero, adarga \
+         antigua, rocín flaco y galgo corredor. Una olla de algo más vaca que \
+         carnero, salpicón las más noches, duelos y quebrantos los sábados, \
+         lantejas los viernes, algún palomino de añadidura los domingos, \
+         consumían las tres partes de su hacienda. El resto della concluían sayo \
+         de velarte, calzas de velludo para las fiestas, con sus pantuflos de lo \
+         mesmo, y los días de entresemana se honraba con su vellorí de lo más \
+         fino. Tenía en su casa una ama que pasaba de los cuarenta, y una \
+         sobrina que no llegaba a los veinte, y un mozo de campo y plaza, que \
+         así ensillaba el rocín como tomaba la podadera. Frisaba la edad de \
+         nuestro hidalgo con los cincuenta años; era de complexión recia, seco \
+         de carnes, enjuto de rostro, gran madrugador y amigo de la caza. \
+         Quieren decir que tenía el sobrenombre de Quijada, o Quesada, que en \
+         esto hay alguna diferencia en los autores que deste caso escriben; \
+         aunque, por conjeturas verosímiles, se deja entender que se llamaba \
+         Quejana. Pero esto importa poco a nuestro cuento; basta que en la \
+         narración dél no se salga un punto de la verdad.";
+
+    fn period_scores(iocs: &[f32]) -> Vec<KeySizeScore> {
+        iocs.iter()
+            .enumerate()
+            .map(|(i, ioc)| {
+                KeySizeScore {
+                    size: i as u32 + 1,
+                    hamming: 0.0,
+                    ioc: *ioc,
+                    guessed: false,
+                }
+            })
+            .collect()
+    }
+
+    fn to_key(key: &str) -> cipher::Key {
+        cipher::Key::from_string(key).unwrap()
+    }
+
     #[test]
     fn test_count_bits() {
         assert_eq!(3, count_set_bits(0b0101010));
@@ -216,44 +1499,420 @@ mod tests {
 
     #[test]
     fn test_guess_key() {
-        check_guess_key("En un lugar de la Mancha, de cuyo nombre no quiero acordarme, no ha \
-                         mucho tiempo que vivía un hidalgo de los de lanza en astillero, adarga \
-                         antigua, rocín flaco y galgo corredor. Una olla de algo más vaca que \
-                         carnero, salpicón las más noches, duelos y quebrantos los sábados, \
-                         lantejas los viernes, algún palomino de añadidura los domingos, \
-                         consumían las tres partes de su hacienda. El resto della concluían sayo \
-                         de velarte, calzas de velludo para las fiestas, con sus pantuflos de lo \
-                         mesmo, y los días de entresemana se honraba con su vellorí de lo más \
-                         fino. Tenía en su casa una ama que pasaba de los cuarenta, y una \
-                         sobrina que no llegaba a los veinte, y un mozo de campo y plaza, que \
-                         así ensillaba el rocín como tomaba la podadera. Frisaba la edad de \
-                         nuestro hidalgo con los cincuenta años; era de complexión recia, seco \
-                         de carnes, enjuto de rostro, gran madrugador y amigo de la caza. \
-                         Quieren decir que tenía el sobrenombre de Quijada, o Quesada, que en \
-                         esto hay alguna diferencia en los autores que deste caso escriben; \
-                         aunque, por conjeturas verosímiles, se deja entender que se llamaba \
-                         Quejana. Pero esto importa poco a nuestro cuento; basta que en la \
-                         narración dél no se salga un punto de la verdad.",
-                        "caracol");
+        check_guess_key(QUIJOTE, "caracol");
+
+    }
+
+    #[test]
+    fn test_decode_parallel() {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt(&plain, &to_key("caracol"));
+        let expected = decode_text(&cipher).unwrap().as_bytes();
+        for threads in 1..5 {
+            let options = ParallelOptions { threads: threads, ..ParallelOptions::default() };
+            let decoded = decode_text_parallel(&cipher, &options, &mut NoProgress).unwrap();
+            assert_eq!(expected, decoded.as_bytes());
+        }
+    }
+
+    #[derive(Default)]
+    struct CountProgress {
+        key_sizes: Vec<u32>,
+        columns: usize,
+        evaluated: usize,
+        best: f32,
+        best_sizes: Vec<u32>,
+    }
+
+    impl Progress for CountProgress {
+        fn started(&mut self, key_sizes: &[u32]) {
+            self.key_sizes = key_sizes.to_vec();
+        }
+
+        fn column_solved(&mut self, _key_size: u32, _column: usize, _columns: usize) {
+            self.columns += 1;
+        }
+
+        fn key_size_evaluated(&mut self, _key_size: u32, _confidence: f32) {
+            self.evaluated += 1;
+        }
+
+        fn best_score(&mut self, key_size: u32, confidence: f32) {
+            assert!(confidence >= self.best);
+            self.best = confidence;
+            self.best_sizes.push(key_size);
+        }
+    }
+
+    #[test]
+    fn test_decode_progress() {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt(&plain, &to_key("caracol"));
+        let mut progress = CountProgress::default();
+        decode_text_parallel(&cipher, &ParallelOptions::default(), &mut progress).unwrap();
+
+        let columns: u32 = progress.key_sizes.iter().sum();
+        assert_eq!(columns as usize, progress.columns);
+        assert_eq!(progress.key_sizes.len(), progress.evaluated);
+        assert!(progress.best > 0.0);
+        // A multiple of the key size decrypts as well, it's reported reduced
+        assert!(progress.best_sizes.iter().all(|size| *size == 7), "{:?}", progress.best_sizes);
+    }
+
+    #[test]
+    fn test_decode_parallel_threshold() {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt(&plain, &to_key("caracol"));
+        let options = ParallelOptions {
+            threads: 2,
+            threshold: Some(0.0),
+            ..ParallelOptions::default()
+        };
+        let mut progress = CountProgress::default();
+        assert!(decode_text_parallel(&cipher, &options, &mut progress).is_ok());
+
+        // Any plain text reaches the threshold, so the first key size evaluated
+        // stops the pool before the others
+        assert!(progress.key_sizes.len() > 1);
+        assert_eq!(1, progress.evaluated);
+    }
+
+    #[test]
+    fn test_decode_report() {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt(&plain, &to_key("caracol"));
+        let options = ParallelOptions::default();
+        let (decoded, report) = decode_text_report(&cipher, &options, &mut NoProgress).unwrap();
 
+        assert_eq!(QUIJOTE, decoded.to_utf8().unwrap());
+        assert_eq!(report.key_size as usize, report.columns.len());
+        assert_eq!("caracol".as_bytes().to_vec(), report.key());
+        assert!(report.columns.iter().all(|column| column.score >= column.runner_up_score));
+        let guesses = guess_key_size(&cipher, options.language).len();
+        assert_eq!(guesses, report.key_sizes.iter().filter(|size| size.guessed).count());
+    }
+
+    #[test]
+    fn test_break_stream() {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let mut input = Vec::new();
+        cipher::encrypt(&plain, &to_key("caracol")).to_writer(&mut input).unwrap();
+
+        let mut output = Vec::new();
+        let options = ParallelOptions::default();
+        break_stream(&input[..], &mut output, &options, &mut NoProgress).unwrap();
+        assert_eq!(QUIJOTE.as_bytes().to_vec(), output);
+    }
+
+    #[test]
+    fn test_too_short() {
+        let cipher = cipher::CipherText::new("abc".as_bytes());
+        match decode_text(&cipher) {
+            Err(cipher::Error::TooShort { len: 3, min: 4 }) => {}
+            _ => panic!("a 3 byte cipher text can't be broken"),
+        }
+        let options = ParallelOptions::default();
+        assert!(decode_text_parallel(&cipher, &options, &mut NoProgress).is_err());
+    }
+
+    #[test]
+    fn test_decode_vigenere() {
+        check_decode_vigenere(QUIJOTE, "caracol");
+        check_decode_vigenere(QUIJOTE, "Dulcinea");
+        check_decode_vigenere(QUIJOTE, "x");
+    }
+
+    #[test]
+    fn test_decode_gronsfeld() {
+        check_decode_gronsfeld("31415");
+        check_decode_gronsfeld("2718");
+    }
+
+    #[test]
+    fn test_decode_porta() {
+        // The key comes back with the first letter of every pair
+        check_decode_porta("lemon", "kemom");
+        check_decode_porta("rocinante", "qocimamse");
+    }
+
+    #[test]
+    fn test_decode_trithemius() {
+        check_decode_trithemius(0);
+        check_decode_trithemius(17);
+        match decode_trithemius(&cipher::CipherText::new("Short".as_bytes()),
+                                Language::Spanish,
+                                &mut NoProgress) {
+            Err(cipher::Error::TooShort { .. }) => {}
+            _ => panic!("a few letters can't be broken"),
+        }
+    }
+
+    #[test]
+    fn test_break_stream_vigenere() {
+        let key = to_key("rocinante");
+        let cipher = cipher::encrypt_vigenere(&cipher::PlainText::from_string(QUIJOTE), &key)
+            .unwrap();
+        let options = ParallelOptions {
+            cipher: CipherKind::Vigenere,
+            language: Language::Spanish,
+            ..ParallelOptions::default()
+        };
+        let mut output = Vec::new();
+        let report = break_stream(&cipher[..], &mut output, &options, &mut NoProgress).unwrap();
+        assert_eq!(QUIJOTE.as_bytes().to_vec(), output);
+        assert_eq!(9, report.key_size);
+
+        match decode_vigenere(&cipher::CipherText::new("Short text".as_bytes()),
+                              Language::Spanish,
+                              &mut NoProgress) {
+            Err(cipher::Error::TooShort { .. }) => {}
+            _ => panic!("a few letters can't be broken"),
+        }
+    }
+
+    #[test]
+    fn test_break_stream_auto() {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let options = ParallelOptions {
+            cipher: CipherKind::Auto,
+            language: Language::Spanish,
+            ..ParallelOptions::default()
+        };
+        let mut xor = Vec::new();
+        cipher::encrypt(&plain, &to_key("caracol")).to_writer(&mut xor).unwrap();
+        let vigenere = cipher::encrypt_vigenere(&plain, &to_key("rocinante")).unwrap();
+        for input in &[&xor[..], &vigenere[..]] {
+            let mut output = Vec::new();
+            break_stream(*input, &mut output, &options, &mut NoProgress).unwrap();
+            assert_eq!(QUIJOTE.as_bytes().to_vec(), output);
+        }
+
+        let mut output = Vec::new();
+        match break_stream(QUIJOTE.as_bytes(), &mut output, &options, &mut NoProgress) {
+            Err(cipher::Error::Failure(_)) => {}
+            _ => panic!("plain text looks like a transposition"),
+        }
+    }
+
+    #[test]
+    fn test_decode_quagmire() {
+        check_decode_quagmire("caracol");
+        check_decode_quagmire("rocin");
+    }
+
+    #[test]
+    fn test_reduce_key() {
+        assert_eq!(3, fundamental_period(b"aceace"));
+        assert_eq!(1, fundamental_period(b"xxxx"));
+        assert_eq!(5, fundamental_period(b"abcab"));
+        assert_eq!(0, fundamental_period(b""));
+
+        // Twice the key size recovers the key twice, reduced to the key
+        let cipher = cipher::encrypt(&cipher::PlainText::from_string(QUIJOTE), &to_key("caracol"));
+        let (plain, keys) = break_columns(&cipher, 14);
+        assert_eq!(QUIJOTE, plain.to_utf8().unwrap());
+        let key: Vec<u8> = reduce_key(keys).iter().map(|key| key.key).collect();
+        assert_eq!("caracol".as_bytes().to_vec(), key);
+    }
+
+    #[test]
+    fn test_guess_periods() {
+        // Size 2 is above the threshold but far from the best one
+        let table = period_scores(&[0.045, 0.055, 0.066, 0.046, 0.045, 0.065]);
+        assert_eq!(vec![3, 6], guess_periods(&[], &table, Language::English));
+
+        // None is above the threshold, the best ones go first
+        let table = period_scores(&[0.040, 0.045, 0.042, 0.050, 0.041, 0.044]);
+        assert_eq!(vec![4, 2, 6, 3], guess_periods(&[], &table, Language::English));
+    }
+
+    #[test]
+    fn test_guess_periods_estimates() {
+        // Both the Friedman estimate and the autocorrelation give 9
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let vigenere = cipher::encrypt_vigenere(&plain, &to_key("rocinante")).unwrap();
+        let text = letters(&vigenere);
+
+        let mut iocs = vec![0.042; 20];
+        iocs[1] = 0.050;
+        iocs[2] = 0.048;
+        iocs[4] = 0.046;
+        assert_eq!(vec![9, 2, 3, 5],
+                   guess_periods(&text, &period_scores(&iocs), Language::Spanish));
+
+        iocs[3] = 0.066;
+        iocs[7] = 0.065;
+        assert_eq!(vec![4, 8, 9],
+                   guess_periods(&text, &period_scores(&iocs), Language::Spanish));
+    }
+
+    #[test]
+    fn test_friedman() {
+        let random = language::RANDOM_LETTER_IOC;
+        let english = Language::English.letter_ioc();
+        let ioc = english / 5.0 + random * 4.0 / 5.0;
+        assert!((friedman_letters(ioc, Language::English).unwrap() - 5.0).abs() < 0.001);
+        assert_eq!(Some(1.0), friedman_letters(english, Language::English));
+        assert_eq!(None, friedman_letters(random, Language::English));
+    }
+
+    #[test]
+    fn test_autocorrelation() {
+        let table = autocorrelation(b"abcabcabca", 4);
+        assert_eq!(4, table.len());
+        assert_eq!(0, table[1].count);
+        assert_eq!(7, table[2].count);
+        assert_eq!(1.0, table[2].rate);
+
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let xor = cipher::encrypt(&plain, &to_key("caracol"));
+        assert_eq!(Some(&7), autocorrelation_periods(&autocorrelation(&xor, 80), 40).first());
+        let vigenere = cipher::encrypt_vigenere(&plain, &to_key("rocinante")).unwrap();
+        let table = autocorrelation(&letters(&vigenere), 80);
+        assert_eq!(Some(&9), autocorrelation_periods(&table, 40).first());
+        assert!(autocorrelation_periods(&autocorrelation(QUIJOTE.as_bytes(), 80), 40).is_empty());
+    }
+
+    #[test]
+    fn test_letter_confidence() {
+        let text = letters(QUIJOTE.as_bytes());
+        assert!(letter_confidence(&text, Language::Spanish) > 0.95);
+        let shifted: Vec<u8> = text.iter().map(|letter| (letter + 3) % 26).collect();
+        assert!(letter_confidence(&shifted, Language::Spanish) < 0.8);
+    }
+
+    #[test]
+    fn test_index_of_coincidence() {
+        assert_eq!(0.0, index_of_coincidence("abcd".as_bytes()));
+        assert_eq!(1.0, index_of_coincidence("aaaa".as_bytes()));
+        assert_eq!(0.5, index_of_coincidence("aaab".as_bytes()));
+    }
+
+    #[test]
+    fn test_detect_xor() {
+        check_detect("Now that the party is jumping", "5");
+        check_detect("this is a slightly longer line that has been encrypted with a longer key",
+                     "ice");
+    }
+
+    #[test]
+    fn test_guess_key_friedman() {
+        // Too short for the Hamming distance and the autocorrelation to find the
+        // key size, the byte coincidence of the whole text still does
+        let plain = cipher::PlainText::from_string("The old lighthouse keeper had lived on the \
+                                                    island for nearly");
+        let cipher = cipher::encrypt(&plain, &to_key("\".1"));
+        let estimate = friedman_bytes(index_of_coincidence(&cipher), Language::English);
+        assert_eq!(Some(3), estimate.map(|length| length.round() as u32));
+        assert!(guess_key_size(&cipher, Language::English).contains(&3));
+    }
+
+    #[test]
+    fn test_guess_key_short_text() {
+        let cipher = cipher::encrypt(&cipher::PlainText::from_string("short"), &to_key("k"));
+        assert_eq!(vec![1], guess_key_size(&cipher, Language::English));
+    }
+
+    fn check_detect(text: &str, key: &str) {
+        let mut ciphers = Vec::new();
+        let mut seed: u32 = 17;
+        for _ in 0..10 {
+            let noise: Vec<u8> = (0..text.len())
+                .map(|_| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    (seed >> 16) as u8
+                })
+                .collect();
+            ciphers.push(cipher::CipherText::new(&noise));
+        }
+        let plain = cipher::PlainText::from_string(text);
+        ciphers.insert(4, cipher::encrypt(&plain, &to_key(key)));
+
+        let detections = detect_xor(&ciphers);
+        assert_eq!(11, detections.len());
+        assert_eq!(4, detections[0].line);
+        assert_eq!(text, detections[0].plain.to_utf8().unwrap());
+    }
+
+    #[test]
+    fn test_detect_reader() {
+        let plain = cipher::PlainText::from_string("Now that the party is jumping");
+        let hex = cipher::encrypt(&plain, &to_key("5")).to_hex();
+        let noise = "8f3ac1d94e27b6500a7fe2913cd84b65f01e9a27c3d8b45e6fa1073c92";
+        let input = format!("{}\nnot hex\n\n{}\n{}\n", noise, hex, noise);
+        let detections = detect_reader(input.as_bytes()).unwrap();
+        assert_eq!(3, detections.len());
+        assert_eq!(4, detections[0].line);
+        assert_eq!("Now that the party is jumping", detections[0].plain.to_utf8().unwrap());
     }
 
     fn check_guess_key(text: &str, key: &str) {
         let plain = cipher::PlainText::from_string(text);
-        let cipher = cipher::encrypt(&plain, key);
-        assert!(guess_key_size(&cipher).contains(&(key.len() as u32)));
+        let cipher = cipher::encrypt(&plain, &to_key(key));
+        assert!(guess_key_size(&cipher, Language::English).contains(&(key.len() as u32)));
+    }
+
+    fn check_decode_vigenere(text: &str, key: &str) {
+        let plain = cipher::PlainText::from_string(text);
+        let cipher = cipher::encrypt_vigenere(&plain, &to_key(key)).unwrap();
+        let (decoded, report) = decode_vigenere(&cipher, Language::Spanish, &mut NoProgress)
+            .unwrap();
+        assert_eq!(text, decoded.to_utf8().unwrap());
+        assert_eq!(key.to_lowercase().into_bytes(), report.key());
+    }
+
+    fn check_decode_gronsfeld(key: &str) {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt_gronsfeld(&plain, &to_key(key)).unwrap();
+        let (decoded, report) = decode_gronsfeld(&cipher, Language::Spanish, &mut NoProgress)
+            .unwrap();
+        assert_eq!(QUIJOTE, decoded.to_utf8().unwrap());
+        assert_eq!(key.as_bytes().to_vec(), report.key());
+    }
+
+    fn check_decode_porta(key: &str, expected: &str) {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt_porta(&plain, &to_key(key)).unwrap();
+        let (decoded, report) = decode_porta(&cipher, Language::Spanish, &mut NoProgress)
+            .unwrap();
+        assert_eq!(QUIJOTE, decoded.to_utf8().unwrap());
+        assert_eq!(expected.as_bytes().to_vec(), report.key());
+    }
+
+    fn check_decode_trithemius(start: u8) {
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt_trithemius(&plain, start);
+        let (decoded, report) = decode_trithemius(&cipher, Language::Spanish, &mut NoProgress)
+            .unwrap();
+        assert_eq!(QUIJOTE, decoded.to_utf8().unwrap());
+        assert_eq!(vec![b'a' + start], report.key());
+    }
+
+    fn check_decode_quagmire(key: &str) {
+        let alphabets = cipher::Alphabets::quagmire(cipher::Quagmire::I, "dulcinea", "toboso")
+            .unwrap();
+        let plain = cipher::PlainText::from_string(QUIJOTE);
+        let cipher = cipher::encrypt_quagmire(&plain, &alphabets, &to_key(key)).unwrap();
+        let (decoded, report) =
+            decode_quagmire(&cipher, Language::Spanish, &mut NoProgress).unwrap();
+        let key_found: Vec<u8> = report.columns.iter().map(|column| column.key).collect();
+        assert_eq!(key.as_bytes(), &key_found[..]);
+        assert_eq!(key.len(), report.key_size as usize);
+        // Rare letters can come out swapped
+        let wrong = QUIJOTE.bytes().zip(decoded.iter()).filter(|&(a, b)| a != *b).count();
+        assert!(wrong < QUIJOTE.len() / 50, "{}", decoded);
     }
 
     fn check_break(text: &str, key: &str) {
         let plain = cipher::PlainText::from_string(text);
-        let cipher = cipher::encrypt(&plain, key);
+        let cipher = cipher::encrypt(&plain, &to_key(key));
         let decoded = break_cipher(&cipher, key.len() as u32);
         assert_eq!(text, decoded.to_utf8().unwrap());
     }
 
     fn check_break_single(text: &str, key: &str) {
         let plain = cipher::PlainText::from_string(text);
-        let cipher = cipher::encrypt(&plain, key);
+        let cipher = cipher::encrypt(&plain, &to_key(key));
         let decoded = decode_single_key(&cipher);
         assert_eq!(text, decoded.to_utf8().unwrap());
     }
@@ -262,6 +1921,24 @@ mod tests {
 
 
 
+    #[test]
+    fn test_hamming_dist_lengths() {
+        let bytes1: Vec<u8> = (0..1000).map(|i| (i * 31 % 251) as u8).collect();
+        let bytes2: Vec<u8> = (0..1000).map(|i| (i * 7 % 256) as u8).collect();
+        for len in &[0, 1, 7, 8, 9, 63, 1000] {
+            check_hamming_dist(&bytes1[..*len], &bytes2[..*len]);
+        }
+        assert_eq!(8, hamming_dist(&[], &[0xff]));
+        assert_eq!(hamming_dist(&[1, 2, 3], &[1, 2]), hamming_dist(&[1, 2], &[1, 2, 3]));
+        assert_eq!(hamming_dist(&bytes1[..10], &bytes2[..10]) + hamming_dist(&[], &bytes2[10..]),
+                   hamming_dist(&bytes1[..10], &bytes2));
+    }
+
+    fn check_hamming_dist(bytes1: &[u8], bytes2: &[u8]) {
+        let expected: u32 = bytes1.iter().zip(bytes2).map(|(a, b)| (a ^ b).count_ones()).sum();
+        assert_eq!(expected as i32, hamming_dist(bytes1, bytes2));
+    }
+
     #[test]
     fn test_hamming_dist() {
         assert_eq!(37,
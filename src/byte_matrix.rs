@@ -25,6 +25,18 @@ impl ByteMatrix {
     }
 
 
+    pub fn from_rows(rows: Vec<Vec<u8>>) -> ByteMatrix {
+        let size = rows.len();
+        ByteMatrix {
+            matrix: rows,
+            row_size: size,
+        }
+    }
+
+    pub fn into_rows(self) -> Vec<Vec<u8>> {
+        self.matrix
+    }
+
     pub fn transform<F>(&self, fun: F) -> ByteMatrix
         where F: FnMut(&Vec<u8>) -> Vec<u8>
     {
@@ -75,6 +87,13 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_rows() {
+        let rows = ByteMatrix::to_matrix("abcdefg".as_bytes(), 3).into_rows();
+        assert_eq!(vec![b"adg".to_vec(), b"be".to_vec(), b"cf".to_vec()], rows);
+        assert_eq!("abcdefg".as_bytes().to_vec(), ByteMatrix::from_rows(rows).reassemble());
+    }
+
     fn check_to_matrix_and_back(text: &str, size: usize) {
         let matrix = ByteMatrix::to_matrix(text.as_bytes(), size);
         let bytes = matrix.reassemble();
@@ -1,21 +1,21 @@
 use rustc_serialize::hex::{ToHex, FromHex, FromHexError};
 use rustc_serialize::base64::{FromBase64Error, ToBase64, FromBase64, STANDARD};
+use super::atomic_file::{check_distinct, AtomicFile, WritePolicy};
+use super::byte_matrix::ByteMatrix;
 use std::str;
 use std::io;
-use std::io::BufWriter;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::io::prelude::*;
 use std::cmp;
-use std::fs::OpenOptions;
-use std::fs;
+use std::error;
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic;
 
-enum WriteMethod {
-    Truncate,
-    Append,
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CipherText(Vec<u8>);
 
 impl CipherText {
@@ -33,6 +33,7 @@ impl CipherText {
         vec_bytes.to_hex()
     }
 
+    /// Copies the bytes, use `as_ref` or deref to borrow them. The copy isn't wiped.
     pub fn as_bytes(&self) -> Vec<u8> {
         self.0.clone()
     }
@@ -51,162 +52,773 @@ impl CipherText {
         buffer_file(path.as_ref()).and_then(CipherText::from_b64)
     }
 
+    /// Reads base64 encoded cipher text until the end of `reader`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<CipherText, Error> {
+        let mut buffer = String::new();
+        try!(reader.read_to_string(&mut buffer));
+        CipherText::from_b64(buffer)
+    }
+
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut file = try!(AtomicFile::create(path, WritePolicy::Overwrite));
+        try!(self.to_writer(&mut file));
+        file.commit()
+    }
+
+    /// Writes the cipher text base64 encoded, in lines of 80 characters.
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
         let text_b64 = self.to_b64();
-        let mut file = try!(create_file(path.as_ref(), WriteMethod::Append));
 
         let mut i = 0;
         let mut j = cmp::min(text_b64.len(), 80);
         while i != j {
-            try!(file.write_all(&text_b64[i..j].as_bytes()));
-            try!(file.write_all("\n".as_bytes()));
+            try!(writer.write_all(&text_b64.as_bytes()[i..j]));
+            try!(writer.write_all("\n".as_bytes()));
 
             i = j;
             j = cmp::min(text_b64.len(), j + 80);
         }
+        try!(writer.flush());
+        Ok(())
+    }
+
+    /// Reads the cipher text as is until the end of `reader`, for the letter
+    /// ciphers whose output isn't base64 encoded.
+    pub fn from_text_reader<R: Read>(mut reader: R) -> Result<CipherText, Error> {
+        let mut bytes = Vec::new();
+        try!(reader.read_to_end(&mut bytes));
+        Ok(CipherText(bytes))
+    }
+
+    /// Writes the cipher text as is, for the letter ciphers.
+    pub fn to_text_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        try!(writer.write_all(&self.0));
+        try!(writer.flush());
         Ok(())
     }
 }
 
+impl AsRef<[u8]> for CipherText {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for CipherText {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for CipherText {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl From<Vec<u8>> for CipherText {
+    fn from(bytes: Vec<u8>) -> CipherText {
+        CipherText(bytes)
+    }
+}
+
+impl From<CipherText> for Vec<u8> {
+    fn from(cipher: CipherText) -> Vec<u8> {
+        cipher.0
+    }
+}
+
+/// Plain text bytes. When `set_wipe_on_drop` is enabled its memory is
+/// overwritten with zeros once it's no longer used, clones keep the setting.
+/// Copies taken with `as_bytes`, `to_utf8` or `Vec::from` aren't wiped.
 #[derive(Clone, Debug)]
-pub struct PlainText(Vec<u8>);
+pub struct PlainText {
+    bytes: Vec<u8>,
+    wipe: bool,
+}
 
 impl PlainText {
     pub fn new(bytes: &[u8]) -> PlainText {
-        PlainText(bytes.to_vec())
+        PlainText::from_vec(bytes.to_vec())
     }
 
     pub fn from_string<S: AsRef<str>>(string: S) -> PlainText {
-        PlainText(string.as_ref().as_bytes().to_vec())
+        PlainText::from_vec(string.as_ref().as_bytes().to_vec())
     }
 
     pub fn from_bytes(bytes: &[u8]) -> PlainText {
-        PlainText(bytes.to_vec())
+        PlainText::from_vec(bytes.to_vec())
     }
 
+    fn from_vec(bytes: Vec<u8>) -> PlainText {
+        PlainText {
+            bytes: bytes,
+            wipe: false,
+        }
+    }
+
+    /// Copies the bytes, use `as_ref` or deref to borrow them.
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.0.clone()
+        self.bytes.clone()
+    }
+
+    pub fn set_wipe_on_drop(&mut self, wipe: bool) {
+        self.wipe = wipe;
+    }
+
+    pub fn wipes_on_drop(&self) -> bool {
+        self.wipe
     }
 
     fn from_file<P: AsRef<Path>>(path: P) -> Result<PlainText, Error> {
-        buffer_file(path.as_ref()).map(PlainText::from_string)
+        let file = try!(File::open(path.as_ref()));
+        PlainText::from_reader(file)
+    }
+
+    /// Reads the plain text until the end of `reader`, it doesn't need to be UTF-8.
+    /// The buffers left behind while reading are wiped.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<PlainText, Error> {
+        let bytes = try!(read_wiping(&mut reader));
+        Ok(PlainText::from_vec(bytes))
     }
 
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
-        let vec_bytes = &self.0;
-        let file = try!(create_file(path.as_ref(), WriteMethod::Truncate));
-        let mut out_file = BufWriter::new(file);
-        try!(out_file.write_all(&vec_bytes));
+        let mut file = try!(AtomicFile::create(path, WritePolicy::Overwrite));
+        try!(self.to_writer(&mut file));
+        file.commit()
+    }
+
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        try!(writer.write_all(&self.bytes));
+        try!(writer.flush());
         Ok(())
     }
 
     pub fn to_utf8(&self) -> Result<String, Error> {
-        let vec_bytes = &self.0;
-        let plain = try!(str::from_utf8(&vec_bytes));
+        let plain = try!(str::from_utf8(&self.bytes));
         Ok(plain.to_string())
     }
 }
 
-fn buffer_file(path: &Path) -> Result<String, Error> {
-    let mut file = try!(File::open(Path::new(path)));
-    let capacity = file.metadata().ok().map_or(0, |x| x.len());
-    let mut buffer = String::with_capacity(capacity as usize);
-    try!(file.read_to_string(&mut buffer));
-    Ok(buffer)
+impl Drop for PlainText {
+    fn drop(&mut self) {
+        self.wipe_if_enabled();
+    }
+}
+
+impl PlainText {
+    fn wipe_if_enabled(&mut self) {
+        if self.wipe {
+            wipe(&mut self.bytes);
+        }
+    }
 }
 
-fn create_file(path: &Path, method: WriteMethod) -> Result<File, Error> {
-    if path.exists() {
-        try!(fs::remove_file(path));
+impl AsRef<[u8]> for PlainText {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Deref for PlainText {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl DerefMut for PlainText {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+/// Two plain texts are equal when they hold the same bytes, wiping isn't compared.
+impl PartialEq for PlainText {
+    fn eq(&self, other: &PlainText) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+/// Shows the text as UTF-8, invalid sequences are replaced with `U+FFFD`.
+impl fmt::Display for PlainText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.bytes))
+    }
+}
+
+impl From<Vec<u8>> for PlainText {
+    fn from(bytes: Vec<u8>) -> PlainText {
+        PlainText::from_vec(bytes)
+    }
+}
+
+/// Takes the bytes out of the plain text, they won't be wiped anymore.
+impl From<PlainText> for Vec<u8> {
+    fn from(mut plain: PlainText) -> Vec<u8> {
+        mem::take(&mut plain.bytes)
+    }
+}
+
+/// Key for the repeating XOR, it can hold any bytes but it's never empty. The
+/// key is wiped from memory on drop, and never shown by `Debug`.
+#[derive(Clone, PartialEq)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    pub fn new(bytes: &[u8]) -> Result<Key, Error> {
+        Key::from_vec(bytes.to_vec())
     }
 
-    match method {
-        WriteMethod::Append => {
-            OpenOptions::new()
-                .write(true)
-                .append(true)
-                .create(true)
-                .open(path)
-                .map_err(Error::from)
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Key, Error> {
+        if bytes.is_empty() {
+            Err(Error::InvalidKey("The key can't be empty".to_string()))
+        } else {
+            Ok(Key(bytes))
         }
-        WriteMethod::Truncate => {
-            OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(path)
-                .map_err(Error::from)
+    }
+
+    pub fn from_string<S: AsRef<str>>(string: S) -> Result<Key, Error> {
+        Key::new(string.as_ref().as_bytes())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Key, Error> {
+        match hex.from_hex() {
+            Ok(bytes) => Key::from_vec(bytes),
+            Err(err) => Err(Error::InvalidKey(err.to_string())),
         }
     }
 
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key(<{} bytes>)", self.0.len())
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+/// Overwrites the whole allocation of the vector with zeros, in a way the
+/// compiler can't optimize away, and leaves it empty.
+pub(crate) fn wipe(bytes: &mut Vec<u8>) {
+    let ptr = bytes.as_mut_ptr();
+    for i in 0..bytes.capacity() {
+        unsafe { ptr::write_volatile(ptr.add(i), 0) };
+    }
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+    bytes.clear();
 }
 
-pub fn encrypt(text: &PlainText, key: &str) -> CipherText {
-    let &PlainText(ref bytes) = text;
-    let xored: Vec<u8> = repeating_xor(bytes, key.as_bytes());
-    CipherText::new(&xored)
+/// Same as `read_to_end`, but the buffers replaced while growing are wiped
+/// instead of being freed with a copy of the data.
+fn read_wiping<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut chunk = vec![0u8; 8192];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                wipe(&mut bytes);
+                wipe(&mut chunk);
+                return Err(Error::from(err));
+            }
+        };
+        if bytes.len() + read > bytes.capacity() {
+            let mut grown = Vec::with_capacity(cmp::max(bytes.capacity() * 2, bytes.len() + read));
+            grown.extend_from_slice(&bytes);
+            wipe(&mut bytes);
+            bytes = grown;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+    }
+    wipe(&mut chunk);
+    Ok(bytes)
 }
 
-pub fn decrypt(cipher_text: &CipherText, key: &str) -> Result<PlainText, Error> {
+fn buffer_file(path: &Path) -> Result<String, Error> {
+    let mut file = try!(File::open(Path::new(path)));
+    let capacity = file.metadata().ok().map_or(0, |x| x.len());
+    let mut buffer = String::with_capacity(capacity as usize);
+    try!(file.read_to_string(&mut buffer));
+    Ok(buffer)
+}
+
+pub fn encrypt(text: &PlainText, key: &Key) -> CipherText {
+    let mut xored: Vec<u8> = text.bytes.clone();
+    repeating_xor_in_place(&mut xored, key);
+    CipherText(xored)
+}
+
+pub fn decrypt(cipher_text: &CipherText, key: &Key) -> Result<PlainText, Error> {
     let &CipherText(ref bytes) = cipher_text;
-    let xored: Vec<u8> = repeating_xor(bytes, key.as_bytes());
-    Ok(PlainText::from_bytes(&xored))
+    let mut xored: Vec<u8> = bytes.clone();
+    repeating_xor_in_place(&mut xored, key);
+    Ok(PlainText::from_vec(xored))
+}
+
+/// Encrypts reusing the buffer of `text`, no copy of the bytes is made.
+pub fn encrypt_in_place(mut text: PlainText, key: &Key) -> CipherText {
+    let mut bytes = mem::take(&mut text.bytes);
+    repeating_xor_in_place(&mut bytes, key);
+    CipherText(bytes)
+}
+
+/// Decrypts reusing the buffer of `cipher_text`, no copy of the bytes is made.
+pub fn decrypt_in_place(cipher_text: CipherText, key: &Key) -> PlainText {
+    let CipherText(mut bytes) = cipher_text;
+    repeating_xor_in_place(&mut bytes, key);
+    PlainText::from_vec(bytes)
 }
 
 pub fn decrypt_single_key(cipher_text: &CipherText, key: u8) -> Result<PlainText, Error> {
     let &CipherText(ref bytes) = cipher_text;
-    let xored: Vec<u8> = repeating_xor(bytes, &[key]);
-    Ok(PlainText::from_bytes(&xored))
+    let mut xored: Vec<u8> = bytes.clone();
+    repeating_xor_in_place(&mut xored, &try!(Key::new(&[key])));
+    Ok(PlainText::from_vec(xored))
 }
 
-pub fn decrypt_file(input_path: &str, output_path: &str, key: &str) -> Result<(), Error> {
+pub fn decrypt_file(input_path: &str, output_path: &str, key: &Key) -> Result<(), Error> {
+    try!(check_distinct(input_path, output_path));
     let cipher = try!(CipherText::from_file(input_path));
-    let plain = try!(decrypt(&cipher, key));
+    let mut plain = decrypt_in_place(cipher, key);
+    plain.set_wipe_on_drop(true);
     try!(plain.to_file(output_path));
     Ok(())
 }
 
-pub fn encrypt_file(input_path: &str, output_path: &str, key: &str) -> Result<(), Error> {
+pub fn encrypt_file(input_path: &str, output_path: &str, key: &Key) -> Result<(), Error> {
+    try!(check_distinct(input_path, output_path));
     let plain = try!(PlainText::from_file(input_path));
-    let cipher = encrypt(&plain, key);
+    let cipher = encrypt_in_place(plain, key);
     try!(cipher.to_file(output_path));
     Ok(())
 }
 
+/// Alphabetic Vigenère: every ASCII letter is shifted forward by the matching
+/// letter of the key, `a` being no shift. The case is kept, and anything that
+/// isn't a letter is copied as is without using up a letter of the key.
+pub fn encrypt_vigenere(text: &PlainText, key: &Key) -> Result<CipherText, Error> {
+    let shifts = try!(letter_key(key));
+    let bytes = map_letters(&text.bytes, |letter, i| (letter + shifts[i % shifts.len()]) % 26);
+    Ok(CipherText(bytes))
+}
+
+pub fn decrypt_vigenere(cipher_text: &CipherText, key: &Key) -> Result<PlainText, Error> {
+    let shifts = try!(letter_key(key));
+    let bytes = map_letters(&cipher_text.0,
+                            |letter, i| (letter + 26 - shifts[i % shifts.len()]) % 26);
+    Ok(PlainText::from_vec(bytes))
+}
+
+/// The Quagmire ciphers of the ACA, Vigenère with keyword mixed alphabets.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Quagmire {
+    /// Keyed plain alphabet and straight cipher alphabet.
+    I,
+    /// Straight plain alphabet and keyed cipher alphabet.
+    II,
+    /// The same keyed alphabet for the plain and the cipher text.
+    III,
+    /// Plain and cipher alphabets keyed with different keywords.
+    IV,
+}
+
+/// Plain and cipher alphabets of a Quagmire cipher, permutations of the letters
+/// from 0 to 25.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alphabets {
+    plain: [u8; 26],
+    cipher: [u8; 26],
+}
+
+impl Alphabets {
+    pub fn new(plain: [u8; 26], cipher: [u8; 26]) -> Result<Alphabets, Error> {
+        if is_permutation(&plain) && is_permutation(&cipher) {
+            Ok(Alphabets {
+                plain: plain,
+                cipher: cipher,
+            })
+        } else {
+            Err(Error::InvalidKey("The alphabets must have every letter once".to_string()))
+        }
+    }
+
+    /// Alphabets of `variant` mixed with `keyword`. Quagmire IV mixes the cipher
+    /// alphabet with `cipher_keyword`, the other variants ignore it.
+    pub fn quagmire(variant: Quagmire,
+                    keyword: &str,
+                    cipher_keyword: &str)
+                    -> Result<Alphabets, Error> {
+        let keyed = try!(keyed_alphabet(keyword));
+        let straight = keyed_alphabet("").unwrap();
+        match variant {
+            Quagmire::I => Alphabets::new(keyed, straight),
+            Quagmire::II => Alphabets::new(straight, keyed),
+            Quagmire::III => Alphabets::new(keyed, keyed),
+            Quagmire::IV => Alphabets::new(keyed, try!(keyed_alphabet(cipher_keyword))),
+        }
+    }
+
+    pub fn plain(&self) -> &[u8; 26] {
+        &self.plain
+    }
+
+    pub fn cipher(&self) -> &[u8; 26] {
+        &self.cipher
+    }
+}
+
+/// Alphabet that starts with the letters of `keyword`, without repeating them,
+/// followed by the rest of the letters in order.
+pub fn keyed_alphabet(keyword: &str) -> Result<[u8; 26], Error> {
+    let mut alphabet = [0; 26];
+    let mut used = [false; 26];
+    let mut len = 0;
+    for byte in keyword.bytes().chain(b'a'..b'z' + 1) {
+        let letter = match letter_index(byte) {
+            Some(letter) => letter,
+            None => return Err(Error::InvalidKey("The keyword can only have letters".to_string())),
+        };
+        if !used[letter as usize] {
+            used[letter as usize] = true;
+            alphabet[len] = letter;
+            len += 1;
+        }
+    }
+    Ok(alphabet)
+}
+
+fn is_permutation(alphabet: &[u8; 26]) -> bool {
+    let mut used = [false; 26];
+    for letter in alphabet.iter() {
+        if *letter >= 26 || used[*letter as usize] {
+            return false;
+        }
+        used[*letter as usize] = true;
+    }
+    true
+}
+
+/// Position of every letter in the alphabet.
+pub fn invert_alphabet(alphabet: &[u8; 26]) -> [u8; 26] {
+    let mut inverse = [0; 26];
+    for (i, letter) in alphabet.iter().enumerate() {
+        inverse[*letter as usize] = i as u8;
+    }
+    inverse
+}
+
+/// Quagmire: for every letter of the key in turn, the cipher alphabet slides
+/// under the plain alphabet until the key letter sits below the plain `a`, and
+/// the plain letter is replaced by the one below it. Case and anything that
+/// isn't a letter are kept as in `encrypt_vigenere`.
+pub fn encrypt_quagmire(text: &PlainText,
+                        alphabets: &Alphabets,
+                        key: &Key)
+                        -> Result<CipherText, Error> {
+    let offsets = try!(quagmire_offsets(alphabets, key));
+    let plain_index = invert_alphabet(&alphabets.plain);
+    let bytes = map_letters(&text.bytes, |letter, i| {
+        let offset = offsets[i % offsets.len()];
+        alphabets.cipher[((plain_index[letter as usize] + offset) % 26) as usize]
+    });
+    Ok(CipherText(bytes))
+}
+
+pub fn decrypt_quagmire(cipher_text: &CipherText,
+                        alphabets: &Alphabets,
+                        key: &Key)
+                        -> Result<PlainText, Error> {
+    let offsets = try!(quagmire_offsets(alphabets, key));
+    let cipher_index = invert_alphabet(&alphabets.cipher);
+    let bytes = map_letters(&cipher_text.0, |letter, i| {
+        let offset = offsets[i % offsets.len()];
+        alphabets.plain[((cipher_index[letter as usize] + 26 - offset) % 26) as usize]
+    });
+    Ok(PlainText::from_vec(bytes))
+}
+
+/// How far the cipher alphabet slides for every letter of the key.
+fn quagmire_offsets(alphabets: &Alphabets, key: &Key) -> Result<Vec<u8>, Error> {
+    let plain_index = invert_alphabet(&alphabets.plain);
+    let cipher_index = invert_alphabet(&alphabets.cipher);
+    let letters = try!(letter_key(key));
+    Ok(letters.iter()
+        .map(|letter| (cipher_index[*letter as usize] + 26 - plain_index[0]) % 26)
+        .collect())
+}
+
+/// Gronsfeld: alphabetic Vigenère with a key of digits, every letter is shifted
+/// forward by the matching digit of the key.
+pub fn encrypt_gronsfeld(text: &PlainText, key: &Key) -> Result<CipherText, Error> {
+    let shifts = try!(digit_key(key));
+    let bytes = map_letters(&text.bytes, |letter, i| (letter + shifts[i % shifts.len()]) % 26);
+    Ok(CipherText(bytes))
+}
+
+pub fn decrypt_gronsfeld(cipher_text: &CipherText, key: &Key) -> Result<PlainText, Error> {
+    let shifts = try!(digit_key(key));
+    let bytes = map_letters(&cipher_text.0,
+                            |letter, i| (letter + 26 - shifts[i % shifts.len()]) % 26);
+    Ok(PlainText::from_vec(bytes))
+}
+
+/// Digits of the key from 0 to 9, the key can only have ASCII digits.
+fn digit_key(key: &Key) -> Result<Vec<u8>, Error> {
+    key.as_bytes()
+        .iter()
+        .map(|byte| if byte.is_ascii_digit() {
+            Ok(byte - b'0')
+        } else {
+            Err(Error::InvalidKey("The key can only have digits".to_string()))
+        })
+        .collect()
+}
+
+/// Porta: the letters of the key are taken in pairs, `a` and `b` being the
+/// first, and every pair swaps the first half of the alphabet with the second
+/// half shifted by the pair number. Encrypting and decrypting are the same.
+pub fn encrypt_porta(text: &PlainText, key: &Key) -> Result<CipherText, Error> {
+    Ok(CipherText(try!(porta(&text.bytes, key))))
+}
+
+pub fn decrypt_porta(cipher_text: &CipherText, key: &Key) -> Result<PlainText, Error> {
+    Ok(PlainText::from_vec(try!(porta(&cipher_text.0, key))))
+}
+
+fn porta(bytes: &[u8], key: &Key) -> Result<Vec<u8>, Error> {
+    let pairs: Vec<u8> = try!(letter_key(key)).iter().map(|letter| letter / 2).collect();
+    Ok(map_letters(bytes, |letter, i| porta_letter(letter, pairs[i % pairs.len()])))
+}
+
+/// Letter from 0 to 25 swapped with `letter` by the Porta key pair `pair`,
+/// from 0 to 12.
+pub fn porta_letter(letter: u8, pair: u8) -> u8 {
+    if letter < 13 {
+        13 + (letter + pair) % 13
+    } else {
+        (letter + 13 - pair) % 13
+    }
+}
+
+/// Trithemius: every letter is shifted forward by its position among the
+/// letters, the first one by `start`. Case and anything that isn't a letter are
+/// kept as in `encrypt_vigenere`.
+pub fn encrypt_trithemius(text: &PlainText, start: u8) -> CipherText {
+    CipherText(map_letters(&text.bytes,
+                           |letter, i| ((letter as usize + start as usize + i) % 26) as u8))
+}
+
+pub fn decrypt_trithemius(cipher_text: &CipherText, start: u8) -> PlainText {
+    PlainText::from_vec(map_letters(&cipher_text.0, |letter, i| {
+        ((letter as usize + 26 - (start as usize + i) % 26) % 26) as u8
+    }))
+}
+
+/// Keyed columnar transposition: the text is written in rows as long as the
+/// key, and the columns are read top to bottom in the order of the key bytes,
+/// equal bytes left to right. Every byte is moved, letters or not.
+pub fn encrypt_columnar(text: &PlainText, key: &Key) -> CipherText {
+    CipherText(transpose_columns(&text.bytes, &column_order(key)))
+}
+
+pub fn decrypt_columnar(cipher_text: &CipherText, key: &Key) -> PlainText {
+    PlainText::from_vec(untranspose_columns(&cipher_text.0, &column_order(key)))
+}
+
+/// Double transposition: columnar transposition with `first`, and again on the
+/// result with `second`.
+pub fn encrypt_double_columnar(text: &PlainText, first: &Key, second: &Key) -> CipherText {
+    let once = transpose_columns(&text.bytes, &column_order(first));
+    CipherText(transpose_columns(&once, &column_order(second)))
+}
+
+pub fn decrypt_double_columnar(cipher_text: &CipherText, first: &Key, second: &Key) -> PlainText {
+    let once = untranspose_columns(&cipher_text.0, &column_order(second));
+    PlainText::from_vec(untranspose_columns(&once, &column_order(first)))
+}
+
+/// Columns in the order they're read, by sorting the key bytes.
+fn column_order(key: &Key) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..key.0.len()).collect();
+    order.sort_by_key(|column| key.0[*column]);
+    order
+}
+
+fn transpose_columns(bytes: &[u8], order: &[usize]) -> Vec<u8> {
+    let columns = ByteMatrix::to_matrix(bytes, order.len()).into_rows();
+    let mut transposed = Vec::with_capacity(bytes.len());
+    for column in order {
+        transposed.extend_from_slice(&columns[*column]);
+    }
+    transposed
+}
+
+fn untranspose_columns(bytes: &[u8], order: &[usize]) -> Vec<u8> {
+    // The first columns of the grid take the bytes of the last, shorter row
+    let (rows, long_columns) = (bytes.len() / order.len(), bytes.len() % order.len());
+    let mut columns = vec![Vec::new(); order.len()];
+    let mut start = 0;
+    for column in order {
+        let len = if *column < long_columns { rows + 1 } else { rows };
+        columns[*column] = bytes[start..start + len].to_vec();
+        start += len;
+    }
+    ByteMatrix::from_rows(columns).reassemble()
+}
+
+/// Letters of the key from 0 to 25, the key can only have ASCII letters.
+fn letter_key(key: &Key) -> Result<Vec<u8>, Error> {
+    key.as_bytes()
+        .iter()
+        .map(|byte| match letter_index(*byte) {
+            Some(letter) => Ok(letter),
+            None => Err(Error::InvalidKey("The key can only have letters".to_string())),
+        })
+        .collect()
+}
+
+/// Position of an ASCII letter in the alphabet, ignoring the case.
+pub fn letter_index(byte: u8) -> Option<u8> {
+    if byte.is_ascii_alphabetic() {
+        Some(byte.to_ascii_lowercase() - b'a')
+    } else {
+        None
+    }
+}
+
+/// Replaces every ASCII letter with `fun(letter, position)`, where `letter` goes
+/// from 0 to 25 and `position` counts only the letters. The case is kept and
+/// anything else is copied as is.
+fn map_letters<F: FnMut(u8, usize) -> u8>(bytes: &[u8], mut fun: F) -> Vec<u8> {
+    let mut position = 0;
+    bytes.iter()
+        .map(|&byte| {
+            let base = if byte.is_ascii_lowercase() {
+                b'a'
+            } else if byte.is_ascii_uppercase() {
+                b'A'
+            } else {
+                return byte;
+            };
+            let letter = fun(byte - base, position);
+            position += 1;
+            base + letter
+        })
+        .collect()
+}
+
+/// Same as `decrypt_file`, reading the base64 cipher text from `input` and
+/// writing the plain text to `output`. The plain text is wiped once written, any
+/// buffer inside `output` is not, as with `Stdout`.
+pub fn decrypt_stream<R: Read, W: Write>(input: R, output: W, key: &Key) -> Result<(), Error> {
+    let cipher = try!(CipherText::from_reader(input));
+    let mut plain = decrypt_in_place(cipher, key);
+    plain.set_wipe_on_drop(true);
+    plain.to_writer(output)
+}
+
+/// Same as `encrypt_file`, reading the plain text from `input` and writing the
+/// base64 cipher text to `output`.
+pub fn encrypt_stream<R: Read, W: Write>(input: R, output: W, key: &Key) -> Result<(), Error> {
+    let plain = try!(PlainText::from_reader(input));
+    let cipher = encrypt_in_place(plain, key);
+    cipher.to_writer(output)
+}
+
 #[derive(Debug)]
 pub enum Error {
-    Hex(String),
-    Base64(String),
-    UTF8(String),
-    File(String),
+    Hex(FromHexError),
+    Base64(FromBase64Error),
+    UTF8(str::Utf8Error),
+    Io(io::Error),
+    InvalidKey(String),
+    /// The output file exists and it mustn't be overwritten.
+    OutputExists(PathBuf),
+    /// The output file is the input file.
+    SameFile(PathBuf),
+    /// The cipher text has `len` bytes, fewer than the `min` needed to break it.
+    TooShort { len: usize, min: usize },
+    /// None of the key sizes tried gave a plausible plain text.
+    NoCandidate,
     Failure(String),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Hex(ref err) => write!(f, "Invalid hex: {}", err),
+            Error::Base64(ref err) => write!(f, "Invalid base64: {}", err),
+            Error::UTF8(ref err) => write!(f, "The text is not valid UTF-8: {}", err),
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::InvalidKey(ref reason) => write!(f, "Invalid key: {}", reason),
+            Error::OutputExists(ref path) => write!(f, "{} already exists", path.display()),
+            Error::SameFile(ref path) => write!(f, "{} is also the input", path.display()),
+            Error::TooShort { len, min } => {
+                write!(f,
+                       "The cipher text is too short to break, it has {} bytes and needs {}",
+                       len,
+                       min)
+            }
+            Error::NoCandidate => write!(f, "Couldn't decode text"),
+            Error::Failure(ref reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Hex(ref err) => Some(err),
+            Error::Base64(ref err) => Some(err),
+            Error::UTF8(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl From<FromBase64Error> for Error {
     fn from(err: FromBase64Error) -> Error {
-        Error::Base64(err.to_string())
+        Error::Base64(err)
     }
 }
 
 impl From<FromHexError> for Error {
     fn from(err: FromHexError) -> Error {
-        Error::Hex(err.to_string())
+        Error::Hex(err)
     }
 }
 
 impl From<str::Utf8Error> for Error {
     fn from(err: str::Utf8Error) -> Error {
-        Error::UTF8(err.to_string())
+        Error::UTF8(err)
     }
 }
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
-        Error::File(err.to_string())
+        Error::Io(err)
     }
 }
 
-fn repeating_xor(input: &[u8], key: &[u8]) -> Vec<u8> {
+/// XORs the input with the key repeated one byte at a time. `repeating_xor_in_place`
+/// gives the same result much faster, this is kept as the reference implementation.
+pub fn repeating_xor(input: &[u8], key: &Key) -> Vec<u8> {
+    let key = key.as_bytes();
     let mut out: Vec<u8> = Vec::with_capacity(input.len());
     for (i, in_val) in input.iter().enumerate() {
         out.push(in_val ^ key[i % key.len()]);
@@ -214,11 +826,54 @@ fn repeating_xor(input: &[u8], key: &[u8]) -> Vec<u8> {
     out
 }
 
+const XOR_BLOCK: usize = 512;
+pub(crate) const WORD: usize = 8;
+
+/// XORs the bytes with the key repeated, without allocating a new buffer. The
+/// key is expanded into a block that holds a whole number of keys and words, so
+/// the bytes are processed a word at a time with no modulo per byte.
+pub fn repeating_xor_in_place(bytes: &mut [u8], key: &Key) {
+    let block = expand_key(key.as_bytes(), cmp::min(bytes.len(), XOR_BLOCK));
+    for chunk in bytes.chunks_mut(block.len()) {
+        xor_words(chunk, &block);
+    }
+}
+
+/// Repeats the key until it's a multiple of the word size at least `min_len` long.
+fn expand_key(key: &[u8], min_len: usize) -> Vec<u8> {
+    let mut len = key.len();
+    while !len.is_multiple_of(WORD) || len < min_len {
+        len += key.len();
+    }
+    key.iter().cycle().take(len).cloned().collect()
+}
+
+/// XORs `bytes` with the start of `block`, which must be at least as long.
+fn xor_words(bytes: &mut [u8], block: &[u8]) {
+    let words_len = bytes.len() / WORD * WORD;
+    let (words, tail) = bytes.split_at_mut(words_len);
+    for (word, key) in words.chunks_mut(WORD).zip(block.chunks(WORD)) {
+        let xored = read_word(word) ^ read_word(key);
+        word.copy_from_slice(&xored.to_ne_bytes());
+    }
+    for (byte, key) in tail.iter_mut().zip(block[words_len..].iter()) {
+        *byte ^= *key;
+    }
+}
+
+/// Reads up to 8 bytes as a word, padding with zeros when there are fewer.
+pub(crate) fn read_word(bytes: &[u8]) -> u64 {
+    let mut word = [0; WORD];
+    word[..bytes.len()].copy_from_slice(bytes);
+    u64::from_ne_bytes(word)
+}
+
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::slice;
 
     #[test]
     fn test_encrypt_and_decrypt() {
@@ -249,19 +904,253 @@ mod tests {
         check_to_from_file("dsklfshdfsfsdfsdfsdfsdfsgfssssssssssssssssssssssfffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff");
     }
 
+    #[test]
+    fn test_encrypt_and_decrypt_streams() {
+        check_stream("This is the plain text\n".as_bytes());
+        check_stream("日本語".as_bytes());
+        check_stream(&[0, 159, 146, 150, 255, 10]);
+        check_stream(&[]);
+    }
+
+    #[test]
+    fn test_binary_file() {
+        let bytes = [0, 159, 146, 150, 255, 10];
+        let path = "./target/test-binary.txt";
+        PlainText::new(&bytes).to_file(path).unwrap();
+        assert_eq!(bytes.to_vec(), PlainText::from_file(path).unwrap().as_bytes());
+    }
+
+    #[test]
+    fn test_same_input_and_output() {
+        let path = "./target/test-same-file.txt";
+        PlainText::from_string("This is the plain text").to_file(path).unwrap();
+        match encrypt_file(path, path, &Key::from_string("toy").unwrap()) {
+            Err(Error::SameFile(_)) => {}
+            _ => panic!("input must not be overwritten"),
+        }
+        assert_eq!("This is the plain text",
+                   PlainText::from_file(path).unwrap().to_utf8().unwrap());
+    }
+
+    #[test]
+    fn test_failure_keeps_output() {
+        let input = "./target/test-bad-cipher.txt";
+        let output = "./target/test-bad-cipher-output.txt";
+        PlainText::from_string("not base64 !!").to_file(input).unwrap();
+        PlainText::from_string("previous output").to_file(output).unwrap();
+        assert!(decrypt_file(input, output, &Key::from_string("toy").unwrap()).is_err());
+        assert_eq!("previous output",
+                   PlainText::from_file(output).unwrap().to_utf8().unwrap());
+    }
+
+    #[test]
+    fn test_binary_key() {
+        let text = "This is the plain text";
+        let key = Key::new(&[0, 255, 13, 10]).unwrap();
+        let cipher_text = encrypt(&PlainText::from_string(text), &key);
+        assert_eq!(text, decrypt(&cipher_text, &key).unwrap().to_utf8().unwrap());
+        assert_eq!(key, Key::from_hex("00ff0d0a").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        check_invalid_key(Key::new(&[]));
+        check_invalid_key(Key::from_string(""));
+        check_invalid_key(Key::from_hex(""));
+        check_invalid_key(Key::from_hex("zz"));
+        check_invalid_key(Key::from_hex("abc"));
+    }
+
+    #[test]
+    fn test_wipe() {
+        let mut bytes = b"secret key".to_vec();
+        bytes.reserve(20);
+        let capacity = bytes.capacity();
+        let ptr = bytes.as_ptr();
+        wipe(&mut bytes);
+        assert!(bytes.is_empty());
+        let memory = unsafe { slice::from_raw_parts(ptr, capacity) };
+        assert!(memory.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_read_wiping() {
+        let text: Vec<u8> = (0..50000).map(|i| (i % 251) as u8).collect();
+        let plain = PlainText::from_reader(&text[..]).unwrap();
+        assert_eq!(text, plain.as_bytes());
+    }
+
+    #[test]
+    fn test_wipe_on_drop() {
+        let mut plain = PlainText::from_string("This is the plain text");
+        assert!(!plain.wipes_on_drop());
+        plain.wipe_if_enabled();
+        assert_eq!(PlainText::from_string("This is the plain text"), plain);
+
+        plain.set_wipe_on_drop(true);
+        let mut clone = plain.clone();
+        assert!(clone.wipes_on_drop());
+        for text in [&mut plain, &mut clone] {
+            let (ptr, capacity) = (text.bytes.as_ptr(), text.bytes.capacity());
+            text.wipe_if_enabled();
+            assert!(text.is_empty());
+            let memory = unsafe { slice::from_raw_parts(ptr, capacity) };
+            assert!(memory.iter().all(|b| *b == 0));
+        }
+    }
+
+    #[test]
+    fn test_key_debug() {
+        let key = Key::from_string("secret").unwrap();
+        assert_eq!("Key(<6 bytes>)", format!("{:?}", key));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let bytes = vec![0, 159, 146, 150, 255, 10];
+        let plain = PlainText::from(bytes.clone());
+        assert_eq!(&bytes[..], plain.as_ref());
+        assert_eq!(bytes.len(), plain.len());
+        assert_eq!(PlainText::new(&bytes), plain);
+        assert_eq!(bytes, Vec::from(plain));
+
+        let cipher = CipherText::from(bytes.clone());
+        assert_eq!(&bytes[..], &cipher[..]);
+        assert_eq!(bytes, Vec::from(cipher));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("日本語", PlainText::from_string("日本語").to_string());
+        assert_eq!("ab\u{fffd}", PlainText::new(&[97, 98, 255]).to_string());
+    }
+
+    #[test]
+    fn test_in_place() {
+        let text = "This is the plain text";
+        let key = Key::from_string("toy").unwrap();
+        let cipher_text = encrypt_in_place(PlainText::from_string(text), &key);
+        assert_eq!(encrypt(&PlainText::from_string(text), &key), cipher_text);
+        assert_eq!(text, decrypt_in_place(cipher_text, &key).to_string());
+    }
+
+    #[test]
+    fn test_xor_in_place() {
+        let input: Vec<u8> = (0..1100).map(|i| (i * 31 % 251) as u8).collect();
+        for key_len in &[1, 3, 8, 13, 64, 600, 2000] {
+            let key: Vec<u8> = (0..*key_len).map(|i| (i * 7 % 256) as u8).collect();
+            for len in &[0, 1, 7, 8, 9, 100, 513, 1100] {
+                check_xor_in_place(&input[..*len], &key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_source() {
+        let err = CipherText::from_b64("not base64 !!").unwrap_err();
+        assert!(error::Error::source(&err).is_some());
+        assert!(err.to_string().starts_with("Invalid base64"));
+
+        match PlainText::from_file("./target/test-missing-file.txt") {
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => {}
+            _ => panic!("the io::Error must be kept"),
+        }
+        assert!(error::Error::source(&Error::NoCandidate).is_none());
+    }
+
+    #[test]
+    fn test_vigenere() {
+        check_vigenere("Attack at dawn!", "lemon", "Lxfopv ef rnhr!");
+        check_vigenere("日本語 abc", "b", "日本語 bcd");
+        check_vigenere("", "key", "");
+        let key = Key::from_string("not a key").unwrap();
+        match encrypt_vigenere(&PlainText::from_string("text"), &key) {
+            Err(Error::InvalidKey(_)) => {}
+            _ => panic!("the key can only have letters"),
+        }
+    }
+
+    #[test]
+    fn test_quagmire() {
+        let keyed: Vec<u8> = keyed_alphabet("KRYPTOS").unwrap().iter().map(|l| l + b'a').collect();
+        assert_eq!(b"kryptosabcdefghijlmnquvwxz".to_vec(), keyed);
+        check_quagmire(Quagmire::I, "zebra", "", "a", "za", "wa");
+        check_quagmire(Quagmire::II, "zebra", "", "a", "ab", "ac");
+        check_quagmire(Quagmire::III, "zebra", "", "b", "za", "xb");
+        for variant in &[Quagmire::I, Quagmire::II, Quagmire::III, Quagmire::IV] {
+            let text = "Attack at dawn! 日本語";
+            let alphabets = Alphabets::quagmire(*variant, "", "").unwrap();
+            let key = Key::from_string("lemon").unwrap();
+            let vigenere = encrypt_vigenere(&PlainText::from_string(text), &key).unwrap();
+            assert_eq!(vigenere,
+                       encrypt_quagmire(&PlainText::from_string(text), &alphabets, &key).unwrap());
+            check_quagmire(*variant, "highway", "automobile", "lemon", text, "");
+        }
+    }
+
+    #[test]
+    fn test_gronsfeld() {
+        check_gronsfeld("Attack at dawn!", "31415", "Duxbhn bx efzo!");
+        check_gronsfeld("zebra 日本語", "0", "zebra 日本語");
+        let key = Key::from_string("31a").unwrap();
+        match encrypt_gronsfeld(&PlainText::from_string("text"), &key) {
+            Err(Error::InvalidKey(_)) => {}
+            _ => panic!("the key can only have digits"),
+        }
+    }
+
+    #[test]
+    fn test_porta() {
+        check_porta("defendtheeastwallofthecastle",
+                    "fortification",
+                    "synnjscvrnrlahutukucvryrlany");
+        check_porta("Attack at dawn!", "lemon", "");
+        // Both letters of a pair are the same key
+        let text = PlainText::from_string("Porta");
+        assert_eq!(encrypt_porta(&text, &Key::from_string("ab").unwrap()).unwrap(),
+                   encrypt_porta(&text, &Key::from_string("ba").unwrap()).unwrap());
+        for letter in 0..26 {
+            assert_eq!(letter, porta_letter(porta_letter(letter, 5), 5));
+        }
+    }
+
+    #[test]
+    fn test_trithemius() {
+        check_trithemius("Attack at dawn!", 0, "Auvdgp ga ljgy!");
+        check_trithemius("zzz", 1, "abc");
+        check_trithemius("日本語", 7, "日本語");
+    }
+
+    #[test]
+    fn test_columnar() {
+        check_columnar("wearediscoveredfleeatonce", "zebras", "evlnacdtesearofodeecwiree");
+        check_columnar("Attack at dawn!", "3142", "tk naaaActwt d!");
+        check_columnar("short", "longer key", "");
+        check_columnar("", "key", "");
+        // Both transpositions undone in the opposite order
+        let text = "Attack at dawn! 日本語";
+        let first = Key::from_string("zebras").unwrap();
+        let second = Key::from_string("lemon").unwrap();
+        let cipher_text = encrypt_double_columnar(&PlainText::from_string(text), &first, &second);
+        let once = encrypt_columnar(&PlainText::from_string(text), &first);
+        assert_eq!(encrypt_columnar(&PlainText::from(Vec::from(once)), &second), cipher_text);
+        assert_eq!(text, decrypt_double_columnar(&cipher_text, &first, &second).to_string());
+    }
+
     #[test]
     fn test_wrong_key() {
         let text = "This is the plain text";
         let plain_text = PlainText::from_string(text);
-        let key: &str = "toy";
-        let decoded_text = decrypt(&encrypt(&plain_text, key), "wrong_key").unwrap();
+        let key = Key::from_string("toy").unwrap();
+        let wrong_key = Key::from_string("wrong_key").unwrap();
+        let decoded_text = decrypt(&encrypt(&plain_text, &key), &wrong_key).unwrap();
         assert!(text != decoded_text.to_utf8().unwrap());
     }
 
     fn check_to_from_file(text: &str) {
         let plain_text = PlainText::from_string(text);
-        let key: &str = "toy";
-        let cipher_text = encrypt(&plain_text, key);
+        let key = Key::from_string("toy").unwrap();
+        let cipher_text = encrypt(&plain_text, &key);
         let path = "./target/debug/test.txt";
 
         plain_text.to_file(&path).unwrap();
@@ -274,23 +1163,104 @@ mod tests {
 
     }
 
+    fn check_vigenere(text: &str, key: &str, expected: &str) {
+        let key = Key::from_string(key).unwrap();
+        let cipher_text = encrypt_vigenere(&PlainText::from_string(text), &key).unwrap();
+        assert_eq!(expected.as_bytes(), &cipher_text[..]);
+        assert_eq!(text, decrypt_vigenere(&cipher_text, &key).unwrap().to_utf8().unwrap());
+    }
+
+    fn check_quagmire(variant: Quagmire,
+                      keyword: &str,
+                      cipher_keyword: &str,
+                      key: &str,
+                      text: &str,
+                      expected: &str) {
+        let alphabets = Alphabets::quagmire(variant, keyword, cipher_keyword).unwrap();
+        let key = Key::from_string(key).unwrap();
+        let cipher_text = encrypt_quagmire(&PlainText::from_string(text), &alphabets, &key)
+            .unwrap();
+        if !expected.is_empty() {
+            assert_eq!(expected.as_bytes(), &cipher_text[..]);
+        }
+        assert_eq!(text,
+                   decrypt_quagmire(&cipher_text, &alphabets, &key).unwrap().to_string());
+    }
+
+    fn check_gronsfeld(text: &str, key: &str, expected: &str) {
+        let key = Key::from_string(key).unwrap();
+        let cipher_text = encrypt_gronsfeld(&PlainText::from_string(text), &key).unwrap();
+        assert_eq!(expected.as_bytes(), &cipher_text[..]);
+        assert_eq!(text, decrypt_gronsfeld(&cipher_text, &key).unwrap().to_string());
+    }
+
+    fn check_porta(text: &str, key: &str, expected: &str) {
+        let key = Key::from_string(key).unwrap();
+        let cipher_text = encrypt_porta(&PlainText::from_string(text), &key).unwrap();
+        if !expected.is_empty() {
+            assert_eq!(expected.as_bytes(), &cipher_text[..]);
+        }
+        assert_eq!(text, decrypt_porta(&cipher_text, &key).unwrap().to_string());
+    }
+
+    fn check_trithemius(text: &str, start: u8, expected: &str) {
+        let cipher_text = encrypt_trithemius(&PlainText::from_string(text), start);
+        assert_eq!(expected.as_bytes(), &cipher_text[..]);
+        assert_eq!(text, decrypt_trithemius(&cipher_text, start).to_string());
+    }
+
+    fn check_columnar(text: &str, key: &str, expected: &str) {
+        let key = Key::from_string(key).unwrap();
+        let cipher_text = encrypt_columnar(&PlainText::from_string(text), &key);
+        if !expected.is_empty() {
+            assert_eq!(expected.as_bytes(), &cipher_text[..]);
+        }
+        assert_eq!(text, decrypt_columnar(&cipher_text, &key).to_string());
+    }
+
+    fn check_xor_in_place(input: &[u8], key: &[u8]) {
+        let key = Key::new(key).unwrap();
+        let mut bytes = input.to_vec();
+        repeating_xor_in_place(&mut bytes, &key);
+        assert_eq!(repeating_xor(input, &key), bytes);
+    }
+
+    fn check_invalid_key(key: Result<Key, Error>) {
+        match key {
+            Err(Error::InvalidKey(_)) => {}
+            _ => panic!("invalid key accepted"),
+        }
+    }
+
+    fn check_stream(bytes: &[u8]) {
+        let key = Key::from_string("toy").unwrap();
+        let mut cipher = Vec::new();
+        encrypt_stream(bytes, &mut cipher, &key).unwrap();
+        assert_eq!(encrypt(&PlainText::new(bytes), &key).to_b64(),
+                   CipherText::from_reader(&cipher[..]).unwrap().to_b64());
+
+        let mut decoded = Vec::new();
+        decrypt_stream(&cipher[..], &mut decoded, &key).unwrap();
+        assert_eq!(bytes.to_vec(), decoded);
+    }
+
     fn check_text(text: &str) {
         let plain_text = PlainText::from_string(text);
-        let key: &str = "toy";
-        let decoded_text = decrypt(&encrypt(&plain_text, key), key).unwrap();
+        let key = Key::from_string("toy").unwrap();
+        let decoded_text = decrypt(&encrypt(&plain_text, &key), &key).unwrap();
         assert_eq!(text, decoded_text.to_utf8().unwrap());
     }
 
     fn check_text_file(text: &str) {
         let plain_text = PlainText::from_string(text);
-        let key: &str = "toy";
+        let key = Key::from_string("toy").unwrap();
         let plain_path = "./target/test-plain.txt";
         let cipher_path = "./target/test-cipher.txt";
         let decoded_path = "./target/test-decoded.txt";
 
         plain_text.to_file(plain_path).unwrap();
-        encrypt_file(plain_path, cipher_path, key).unwrap();
-        decrypt_file(cipher_path, decoded_path, key).unwrap();
+        encrypt_file(plain_path, cipher_path, &key).unwrap();
+        decrypt_file(cipher_path, decoded_path, &key).unwrap();
         let decoded_text = PlainText::from_file(decoded_path).unwrap();
 
         assert_eq!(text, decoded_text.to_utf8().unwrap());
@@ -0,0 +1,337 @@
+use super::analysis;
+use super::breaker::{self, CipherKind};
+use super::cipher;
+use super::language::{self, Language};
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::str;
+
+/// Index of coincidence of the bytes of plain text, spaces and punctuation
+/// included. Random bytes have 1/256.
+const TEXT_BYTE_IOC: f32 = 0.06;
+
+/// Fraction of printable bytes below which the input is judged as binary.
+const MIN_TEXT_PRINTABLE: f32 = 0.9;
+
+/// Fraction of the highest column index of coincidence a shorter XOR key size
+/// needs to be preferred.
+const KEY_SIZE_IOC_RATIO: f32 = 0.9;
+
+/// Least letters, or bytes for binary input, worth looking at.
+const MIN_IDENTIFY_LEN: usize = 20;
+
+/// `letter_confidence` of random letters, and of text in the language.
+const RANDOM_CONFIDENCE: f32 = 0.6;
+const TEXT_CONFIDENCE: f32 = 0.95;
+
+/// Kind of cipher a text looks encrypted with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CipherType {
+    /// The letters of the language moved around, plain text looks the same.
+    Transposition,
+    /// Every letter always replaced with the same one, as Caesar.
+    Monoalphabetic,
+    /// Letters replaced depending on the position, as Vigenère, with the period.
+    Polyalphabetic(u32),
+    /// Bytes XOR'ed with a repeating key, with the key size.
+    RepeatingXor(u32),
+    /// Nothing to tell it from random letters or bytes.
+    Random,
+}
+
+impl CipherType {
+    /// Attack `breaker::decode_with` breaks this kind of cipher with, if any.
+    /// Monoalphabetic substitution is Quagmire I with a single key letter.
+    pub fn attack(&self) -> Option<CipherKind> {
+        match *self {
+            CipherType::Monoalphabetic => Some(CipherKind::Quagmire),
+            CipherType::Polyalphabetic(_) => Some(CipherKind::Vigenere),
+            CipherType::RepeatingXor(_) => Some(CipherKind::Xor),
+            CipherType::Transposition | CipherType::Random => None,
+        }
+    }
+}
+
+impl fmt::Display for CipherType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CipherType::Transposition => write!(f, "transposition"),
+            CipherType::Monoalphabetic => write!(f, "monoalphabetic substitution"),
+            CipherType::Polyalphabetic(period) => {
+                write!(f, "periodic polyalphabetic, period {}", period)
+            }
+            CipherType::RepeatingXor(size) => write!(f, "repeating XOR, key size {}", size),
+            CipherType::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// A cipher type the text could be encrypted with, with a score from 0 to 1
+/// and the statistics that back it.
+#[derive(Clone, Debug)]
+pub struct Hypothesis {
+    pub cipher: CipherType,
+    pub score: f32,
+    pub evidence: Vec<String>,
+}
+
+/// Guesses what the input is encrypted with, most likely first. Text is judged
+/// on its letters: the index of coincidence tells substitutions keeping the
+/// letter frequencies of the language apart from periodic ciphers and random
+/// letters, and the frequencies themselves tell transposition from
+/// substitution. Binary input, or base64 without spaces, is judged on its bytes
+/// for repeating XOR. Input that reads both ways gets both sets of hypotheses,
+/// with their scores halved, and a type found both ways adds up its scores.
+pub fn identify(input: &[u8], language: Language) -> Vec<Hypothesis> {
+    let binary = as_binary(input);
+    let text = binary.is_none() || is_letters(input);
+    let mut hypotheses = Vec::new();
+    if text {
+        hypotheses = identify_letters(input, language);
+        if binary.is_some() {
+            for hypothesis in hypotheses.iter_mut() {
+                hypothesis.score /= 2.0;
+            }
+        }
+    }
+    if let Some(bytes) = binary {
+        for mut hypothesis in identify_bytes(&bytes, language) {
+            if text {
+                hypothesis.score /= 2.0;
+            }
+            match hypotheses.iter().position(|other| other.cipher == hypothesis.cipher) {
+                Some(i) => {
+                    hypotheses[i].score += hypothesis.score;
+                    hypotheses[i].evidence.extend(hypothesis.evidence);
+                }
+                None => hypotheses.push(hypothesis),
+            }
+        }
+    }
+    hypotheses.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    hypotheses
+}
+
+/// Bytes of the input as binary cipher text: base64 decoded if it's base64
+/// without spaces, as is if it's mostly unprintable, and `None` otherwise.
+pub fn as_binary(input: &[u8]) -> Option<cipher::CipherText> {
+    match str::from_utf8(input) {
+        Ok(text) if is_base64(text) => cipher::CipherText::from_b64(text).ok(),
+        _ if printable(input) >= MIN_TEXT_PRINTABLE => None,
+        _ => Some(cipher::CipherText::new(input)),
+    }
+}
+
+/// The single reading of the input to gather statistics on: letters only
+/// text is kept as is, even if it's valid base64, anything else goes
+/// through `as_binary`.
+pub fn binary_or_text(input: cipher::CipherText) -> cipher::CipherText {
+    if is_letters(&input) {
+        input
+    } else {
+        as_binary(&input).unwrap_or(input)
+    }
+}
+
+/// Fraction of the bytes that are printable ASCII or whitespace.
+fn printable(bytes: &[u8]) -> f32 {
+    let count = bytes.iter()
+        .filter(|byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace())
+        .count();
+    count as f32 / cmp::max(bytes.len(), 1) as f32
+}
+
+fn is_base64(text: &str) -> bool {
+    let stripped: Vec<u8> = text.bytes().filter(|byte| !b"\r\n".contains(byte)).collect();
+    !stripped.is_empty() && stripped.len().is_multiple_of(4) &&
+    stripped.iter().all(|byte| byte.is_ascii_alphanumeric() || b"+/=".contains(byte))
+}
+
+fn is_letters(input: &[u8]) -> bool {
+    input.iter().all(|byte| byte.is_ascii_alphabetic() || byte.is_ascii_whitespace())
+}
+
+/// How far `value` is from `low` towards `high`, from 0 to 1.
+fn closeness(value: f32, low: f32, high: f32) -> f32 {
+    ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+fn identify_letters(input: &[u8], language: Language) -> Vec<Hypothesis> {
+    let letters = breaker::letters(input);
+    if letters.len() < MIN_IDENTIFY_LEN {
+        return Vec::new();
+    }
+    let ioc = breaker::index_of_coincidence(&letters);
+    let ioc_evidence = format!("letter index of coincidence {:.4}, {:?} {:.4}, random {:.4}",
+                               ioc,
+                               language,
+                               language.letter_ioc(),
+                               language::RANDOM_LETTER_IOC);
+    let monoalphabetic = closeness(ioc, language::RANDOM_LETTER_IOC, language.letter_ioc());
+    let confidence = breaker::letter_confidence(&letters, language);
+    let confidence_evidence = format!("letter frequencies match {:?} at {:.2}, random letters at \
+                                       about {:.2}",
+                                      language,
+                                      confidence,
+                                      RANDOM_CONFIDENCE);
+    let unshifted = closeness(confidence, RANDOM_CONFIDENCE, TEXT_CONFIDENCE);
+    let entropy_evidence = format!("{:.2} bits of entropy per letter, {:.2} at most",
+                                   analysis::entropy(&letters),
+                                   26f32.log2());
+
+    // Periods above 1, the same letter ciphers as the breaker tries
+    let table: Vec<_> = breaker::period_table(&letters).into_iter().skip(1).collect();
+    let period = breaker::guess_periods(&letters, &table, language).first().cloned();
+    let period_ioc = period.map_or(language::RANDOM_LETTER_IOC,
+                                   |period| table[period as usize - 2].ioc);
+    let periodic = closeness(period_ioc, language::RANDOM_LETTER_IOC, language.letter_ioc());
+    let period_evidence = match period {
+        Some(period) => {
+            format!("columns of period {} have an index of coincidence of {:.4}",
+                    period,
+                    period_ioc)
+        }
+        None => "too few letters to look for a period".to_string(),
+    };
+    let friedman_evidence = match breaker::friedman_letters(ioc, language) {
+        Some(length) => format!("Friedman estimate of the key length {:.1}", length),
+        None => "no Friedman estimate of the key length, the letters look random".to_string(),
+    };
+
+    let mut hypotheses = vec![Hypothesis {
+                                  cipher: CipherType::Transposition,
+                                  score: monoalphabetic * unshifted,
+                                  evidence: vec![ioc_evidence.clone(),
+                                                 confidence_evidence.clone()],
+                              },
+                              Hypothesis {
+                                  cipher: CipherType::Monoalphabetic,
+                                  score: monoalphabetic * (1.0 - unshifted),
+                                  evidence: vec![ioc_evidence.clone(), confidence_evidence],
+                              },
+                              Hypothesis {
+                                  cipher: CipherType::Random,
+                                  score: (1.0 - monoalphabetic) * (1.0 - periodic),
+                                  evidence: vec![ioc_evidence.clone(),
+                                                 period_evidence.clone(),
+                                                 entropy_evidence],
+                              }];
+    if let Some(period) = period {
+        hypotheses.push(Hypothesis {
+            cipher: CipherType::Polyalphabetic(period),
+            score: (1.0 - monoalphabetic) * periodic,
+            evidence: vec![ioc_evidence, period_evidence, friedman_evidence],
+        });
+    }
+    hypotheses
+}
+
+fn identify_bytes(bytes: &[u8], language: Language) -> Vec<Hypothesis> {
+    if bytes.len() < MIN_IDENTIFY_LEN {
+        return Vec::new();
+    }
+    let guesses = breaker::guess_key_size(&cipher::CipherText::new(bytes), language);
+    let table = breaker::key_size_table(bytes, &guesses);
+    // Multiples of the key size score as high, keep the shortest one close to the best
+    let highest = table.iter().map(|score| score.ioc).fold(0.0, f32::max);
+    let best = match table.into_iter().find(|score| score.ioc >= highest * KEY_SIZE_IOC_RATIO) {
+        Some(best) => best,
+        None => return Vec::new(),
+    };
+    // Text XOR'ed with a key of printable characters is mostly control bytes,
+    // while text left alone is mostly printable
+    let printable = printable(bytes);
+    let columns = closeness(best.ioc, 1.0 / 256.0, TEXT_BYTE_IOC);
+    let evidence = vec![format!("columns of key size {} have a byte index of coincidence of \
+                                 {:.4}, text {:.4}, random {:.4}",
+                                best.size,
+                                best.ioc,
+                                TEXT_BYTE_IOC,
+                                1.0 / 256.0),
+                        format!("normalized Hamming distance {:.4} between blocks of {} bytes, \
+                                 the best key sizes by Hamming distance are {:?}",
+                                best.hamming,
+                                best.size,
+                                guesses),
+                        format!("{:.0}% of the bytes are printable", printable * 100.0),
+                        format!("{:.2} bits of entropy per byte", analysis::entropy(bytes))];
+    vec![Hypothesis {
+             cipher: CipherType::RepeatingXor(best.size),
+             score: columns * (1.0 - printable),
+             evidence: evidence.clone(),
+         },
+         Hypothesis {
+             cipher: CipherType::Random,
+             score: 1.0 - columns,
+             evidence: evidence,
+         }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::cipher::{self, Key, PlainText};
+
+    const SAMPLE: &str = include_str!("../data/spanish.txt");
+
+    #[test]
+    fn test_identify() {
+        let text: String = SAMPLE.chars().take(1500).collect();
+        let plain = PlainText::from_string(&text);
+        let key = |key: &str| Key::from_string(key).unwrap();
+        check_identify(text.as_bytes(), CipherType::Transposition);
+        let columnar = cipher::encrypt_columnar(&plain, &key("zebras"));
+        check_identify(&columnar, CipherType::Transposition);
+        let caesar = cipher::encrypt_vigenere(&plain, &key("k")).unwrap();
+        check_identify(&caesar, CipherType::Monoalphabetic);
+        let vigenere = cipher::encrypt_vigenere(&plain, &key("rocinante")).unwrap();
+        check_identify(&vigenere, CipherType::Polyalphabetic(9));
+        let xor = cipher::encrypt(&plain, &key("secret"));
+        check_identify(xor.to_b64().as_bytes(), CipherType::RepeatingXor(6));
+        check_identify(&xor, CipherType::RepeatingXor(6));
+
+        let mut state = 7u32;
+        let random: Vec<u8> = (0..1500)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 8) as u8
+            })
+            .collect();
+        check_identify(&random, CipherType::Random);
+        let letters: Vec<u8> = random.iter().map(|byte| b'a' + byte % 26).collect();
+        check_identify(&letters, CipherType::Random);
+
+        assert!(identify(b"too short", Language::Spanish).is_empty());
+    }
+
+    #[test]
+    fn test_attack() {
+        assert_eq!(Some(CipherKind::Vigenere), CipherType::Polyalphabetic(3).attack());
+        assert_eq!(Some(CipherKind::Xor), CipherType::RepeatingXor(3).attack());
+        assert_eq!(None, CipherType::Transposition.attack());
+        assert_eq!(None, CipherType::Random.attack());
+    }
+
+    #[test]
+    fn test_as_binary() {
+        assert_eq!(Some(b"hi!".to_vec()), as_binary(b"aGkh\n").map(|bytes| bytes.as_bytes()));
+        assert_eq!(Some(vec![0xff, 0x00]), as_binary(&[0xff, 0x00]).map(|bytes| bytes.as_bytes()));
+        assert_eq!(None, as_binary(b"Not base64").map(|bytes| bytes.as_bytes()));
+    }
+
+    #[test]
+    fn test_binary_or_text() {
+        let letters = cipher::CipherText::new(b"LXFOPVEFRNHR");
+        assert_eq!(letters.as_bytes(), binary_or_text(letters.clone()).as_bytes());
+        let base64 = cipher::CipherText::new(b"AP8=");
+        assert_eq!(vec![0x00, 0xff], binary_or_text(base64).as_bytes());
+    }
+
+    fn check_identify(input: &[u8], expected: CipherType) {
+        let hypotheses = identify(input, Language::Spanish);
+        assert_eq!(expected, hypotheses[0].cipher, "{:?}", hypotheses);
+        assert!(!hypotheses[0].evidence.is_empty());
+    }
+}
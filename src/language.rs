@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+/// Language of the plain text, used by the letter cipher breakers.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+/// Relative frequency of the letters a to z in English text.
+const ENGLISH: [f32; 26] = [0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015,
+                            0.06094, 0.06966, 0.00153, 0.00772, 0.04025, 0.02406, 0.06749,
+                            0.07507, 0.01929, 0.00095, 0.05987, 0.06327, 0.09056, 0.02758,
+                            0.00978, 0.02360, 0.00150, 0.01974, 0.00074];
+
+/// Relative frequency of the letters a to z in Spanish text. Accented letters
+/// aren't ASCII so they aren't counted.
+const SPANISH: [f32; 26] = [0.11525, 0.02215, 0.04019, 0.05010, 0.12181, 0.00692, 0.01768,
+                            0.00703, 0.06247, 0.00493, 0.00011, 0.04967, 0.03157, 0.06712,
+                            0.08683, 0.02510, 0.00877, 0.06871, 0.07977, 0.04632, 0.02927,
+                            0.01138, 0.00017, 0.00215, 0.01008, 0.00467];
+
+/// Sample texts the letter pair statistics are counted on.
+const ENGLISH_SAMPLE: &str = include_str!("../data/english.txt");
+const SPANISH_SAMPLE: &str = include_str!("../data/spanish.txt");
+
+impl Language {
+    /// Probability of every letter a to z, they add up to 1.
+    pub fn letter_frequencies(&self) -> [f32; 26] {
+        let table = match *self {
+            Language::English => &ENGLISH,
+            Language::Spanish => &SPANISH,
+        };
+        let total: f32 = table.iter().sum();
+        let mut frequencies = [0.0; 26];
+        for (frequency, value) in frequencies.iter_mut().zip(table.iter()) {
+            *frequency = value / total;
+        }
+        frequencies
+    }
+
+    /// Index of coincidence of the letters of a text in the language, the
+    /// probability that two letters picked at random are the same.
+    pub fn letter_ioc(&self) -> f32 {
+        self.letter_frequencies().iter().map(|f| f * f).sum()
+    }
+
+    /// Index of coincidence of the bytes of a text in the language, counted on
+    /// the sample text with its spaces, punctuation and case.
+    pub fn byte_ioc(&self) -> f32 {
+        let mut counts = [0u64; 256];
+        for byte in self.sample().bytes() {
+            counts[byte as usize] += 1;
+        }
+        let pairs: u64 = counts.iter().map(|n| n * n.saturating_sub(1)).sum();
+        let len = self.sample().len() as u64;
+        pairs as f32 / (len * (len - 1)) as f32
+    }
+
+    /// Natural log of the probability of every pair of consecutive letters,
+    /// indexed by `first * 26 + second`. They're counted on a sample text in the
+    /// language skipping everything that isn't a letter, and pairs missing from
+    /// the sample count as seen once.
+    pub fn bigram_log_probabilities(&self) -> Vec<f32> {
+        let mut counts = vec![1.0f32; 26 * 26];
+        let mut previous = None;
+        for byte in self.sample().bytes().filter(u8::is_ascii_alphabetic) {
+            let letter = (byte.to_ascii_lowercase() - b'a') as usize;
+            if let Some(first) = previous {
+                counts[first * 26 + letter] += 1.0;
+            }
+            previous = Some(letter);
+        }
+        let total: f32 = counts.iter().sum();
+        counts.iter().map(|count| (count / total).ln()).collect()
+    }
+
+    fn sample(&self) -> &'static str {
+        match *self {
+            Language::English => ENGLISH_SAMPLE,
+            Language::Spanish => SPANISH_SAMPLE,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Language, String> {
+        match name {
+            "english" | "en" => Ok(Language::English),
+            "spanish" | "es" => Ok(Language::Spanish),
+            _ => Err(format!("Unknown language {}", name)),
+        }
+    }
+}
+
+/// Probability that two letters picked at random from uniformly random text
+/// are the same.
+pub const RANDOM_LETTER_IOC: f32 = 1.0 / 26.0;
+
+/// Probability that two bytes picked at random from uniformly random bytes are
+/// the same.
+pub const RANDOM_BYTE_IOC: f32 = 1.0 / 256.0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letter_ioc() {
+        check_letter_ioc(Language::English, 0.0655);
+        check_letter_ioc(Language::Spanish, 0.0718);
+    }
+
+    #[test]
+    fn test_byte_ioc() {
+        assert!((Language::English.byte_ioc() - 0.0751).abs() < 0.002);
+        assert!((Language::Spanish.byte_ioc() - 0.0727).abs() < 0.002);
+    }
+
+    #[test]
+    fn test_bigrams() {
+        let english = Language::English.bigram_log_probabilities();
+        assert_eq!(26 * 26, english.len());
+        assert!(english[bigram("th")] > english[bigram("ht")]);
+        let spanish = Language::Spanish.bigram_log_probabilities();
+        assert!(spanish[bigram("qu")] > spanish[bigram("qa")]);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Ok(Language::Spanish), "spanish".parse());
+        assert_eq!(Ok(Language::English), "en".parse());
+        assert!("klingon".parse::<Language>().is_err());
+    }
+
+    fn bigram(pair: &str) -> usize {
+        let bytes = pair.as_bytes();
+        (bytes[0] - b'a') as usize * 26 + (bytes[1] - b'a') as usize
+    }
+
+    fn check_letter_ioc(language: Language, expected: f32) {
+        let ioc = language.letter_ioc();
+        assert!((ioc - expected).abs() < 0.002, "{:?} {}", language, ioc);
+    }
+}
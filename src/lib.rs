@@ -1,5 +1,11 @@
-#![feature(inclusive_range_syntax)]
 extern crate rustc_serialize;
 pub mod cipher;
 pub mod breaker;
 pub mod byte_matrix;
+pub mod report;
+pub mod atomic_file;
+pub mod language;
+pub mod pipeline;
+pub mod identify;
+pub mod analysis;
+mod pool;
@@ -1,46 +1,581 @@
 extern crate vigenere;
-extern crate getopts;
+#[macro_use]
+extern crate clap;
+extern crate rustc_serialize;
+extern crate rpassword;
 
-use vigenere::cipher::{decrypt_file, encrypt_file};
-use vigenere::breaker::break_file;
-use getopts::*;
+use vigenere::atomic_file::{check_distinct, AtomicFile, WritePolicy};
+use vigenere::cipher::{self, decrypt_stream, encrypt_stream, Error, Key};
+use vigenere::report::BreakReport;
+use vigenere::breaker::{self, break_stream, detect_reader, NoProgress, ParallelOptions, Progress};
+use vigenere::pipeline::Pipeline;
+use vigenere::identify;
+use vigenere::analysis::Analysis;
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
 use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut opts = Options::new();
+const BAR_WIDTH: usize = 40;
+
+/// Draws a bar with the solved columns on stderr, so it doesn't mix with the
+/// program output.
+#[derive(Default)]
+struct ProgressBar {
+    total: usize,
+    solved: usize,
+    best: f32,
+}
+
+impl ProgressBar {
+    fn draw(&self) {
+        let filled = (self.solved * BAR_WIDTH).checked_div(self.total).unwrap_or(BAR_WIDTH);
+        let _ = write!(io::stderr(),
+                       "\r[{}{}] {}/{} columns, best confidence {:.2}",
+                       "#".repeat(filled),
+                       " ".repeat(BAR_WIDTH - filled),
+                       self.solved,
+                       self.total,
+                       self.best);
+    }
 
-    opts.optopt("i", "", "Input file", "INPUT_FILE");
-    opts.optopt("o", "", "Output file", "OUTPUT_FILE");
-    opts.optopt("k", "", "Key", "KEY_STRING");
-    opts.optflag("d", "", "Decrypt");
-    opts.optflag("b", "", "Break cipher without key");
+    fn finish(&self) {
+        let _ = writeln!(io::stderr());
+    }
+}
 
-    let matches = opts.parse(&args[1..]).unwrap();
-    let input = matches.opt_str("i").unwrap();
-    let output = matches.opt_str("o").unwrap();
-    let decrypt = matches.opt_present("d");
-    let break_cipher = matches.opt_present("b");
+impl Progress for ProgressBar {
+    fn started(&mut self, key_sizes: &[u32]) {
+        self.total = key_sizes.iter().sum::<u32>() as usize;
+        self.draw();
+    }
+
+    fn column_solved(&mut self, _key_size: u32, _column: usize, _columns: usize) {
+        self.solved += 1;
+        self.draw();
+    }
+
+    fn best_score(&mut self, _key_size: u32, confidence: f32) {
+        self.best = confidence;
+        self.draw();
+    }
+}
+
+/// Prints one JSON object per progress event on stdout, or stderr when `stderr`
+/// is set, for scripts driving the binary.
+#[derive(Clone)]
+struct JsonProgress {
+    stderr: bool,
+}
 
-    if break_cipher {
-        match break_file(&input, &output) {
-            Ok(_) => println!("Done!"),
-            Err(err) => println!("Error found: {:?}", err),
+impl JsonProgress {
+    fn emit(&self, event: &str, fields: Vec<(&str, Json)>) {
+        let mut object = BTreeMap::new();
+        object.insert("event".to_string(), event.to_json());
+        for (name, value) in fields {
+            object.insert(name.to_string(), value);
         }
+        let json = Json::Object(object);
+        if self.stderr {
+            let _ = writeln!(io::stderr(), "{}", json);
+        } else {
+            let _ = writeln!(io::stdout(), "{}", json);
+        }
+    }
+}
+
+impl Progress for JsonProgress {
+    fn started(&mut self, key_sizes: &[u32]) {
+        self.emit("started", vec![("key_sizes", key_sizes.to_json())]);
+    }
+
+    fn column_solved(&mut self, key_size: u32, column: usize, columns: usize) {
+        self.emit("column_solved",
+                  vec![("key_size", key_size.to_json()),
+                       ("column", column.to_json()),
+                       ("columns", columns.to_json())]);
+    }
 
-    } else if decrypt {
-        let key = matches.opt_str("k").unwrap();
-        match decrypt_file(&input, &output, &key) {
-            Ok(_) => println!("Done!"),
-            Err(err) => println!("Error found: {:?}", err),
+    fn key_size_evaluated(&mut self, key_size: u32, confidence: f32) {
+        self.emit("key_size_evaluated",
+                  vec![("key_size", key_size.to_json()), ("confidence", confidence.to_json())]);
+    }
+
+    fn best_score(&mut self, key_size: u32, confidence: f32) {
+        self.emit("best_score",
+                  vec![("key_size", key_size.to_json()), ("confidence", confidence.to_json())]);
+    }
+}
+
+fn write_report(report: &BreakReport, path: &str, policy: WritePolicy) -> Result<(), Error> {
+    let contents = if path.ends_with(".json") {
+        report.to_json().pretty().to_string()
+    } else {
+        report.to_markdown()
+    };
+    let mut file = try!(AtomicFile::create(path, policy));
+    try!(file.write_all(contents.as_bytes()));
+    file.commit()
+}
+
+fn input_arg() -> Arg<'static, 'static> {
+    Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .value_name("INPUT_FILE")
+        .help("Input file, - for stdin")
+        .takes_value(true)
+        .default_value("-")
+}
+
+fn output_arg() -> Arg<'static, 'static> {
+    Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .value_name("OUTPUT_FILE")
+        .help("Output file, - for stdout")
+        .takes_value(true)
+        .default_value("-")
+}
+
+fn non_empty(value: String) -> Result<(), String> {
+    if value.is_empty() {
+        Err("the key can't be empty".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// The key can come from one of these options, or from an interactive prompt
+/// when none of them is given, keeping it out of the shell history.
+fn key_args() -> Vec<Arg<'static, 'static>> {
+    vec![Arg::with_name("key")
+             .short("k")
+             .long("key")
+             .value_name("KEY_STRING")
+             .help("Key")
+             .takes_value(true)
+             .validator(non_empty)
+             .conflicts_with_all(&["key-file", "key-env", "key-hex"]),
+         Arg::with_name("key-file")
+             .long("key-file")
+             .value_name("KEY_FILE")
+             .help("Read the key from a file, ignoring a trailing newline")
+             .takes_value(true)
+             .conflicts_with_all(&["key-env", "key-hex"]),
+         Arg::with_name("key-env")
+             .long("key-env")
+             .value_name("VAR")
+             .help("Read the key from an environment variable")
+             .takes_value(true)
+             .conflicts_with("key-hex"),
+         Arg::with_name("key-hex")
+             .long("key-hex")
+             .value_name("HEX_KEY")
+             .help("Hex encoded binary key")
+             .takes_value(true)
+             .validator(non_empty)]
+}
+
+fn read_key(matches: &ArgMatches, confirm: bool) -> Result<Key, Error> {
+    if let Some(key) = matches.value_of("key") {
+        Key::from_string(key)
+    } else if let Some(path) = matches.value_of("key-file") {
+        // Sized up front so no copies of the key are left behind by reallocations
+        let mut file = try!(File::open(path));
+        let size = try!(file.metadata()).len() as usize;
+        let mut key = Vec::with_capacity(size + 1);
+        try!(file.read_to_end(&mut key));
+        if key.ends_with(b"\n") {
+            key.pop();
+            if key.ends_with(b"\r") {
+                key.pop();
+            }
         }
+        Key::from_vec(key)
+    } else if let Some(var) = matches.value_of("key-env") {
+        match env::var(var) {
+            Ok(key) => Key::from_vec(key.into_bytes()),
+            Err(_) => Err(Error::InvalidKey(format!("The variable {} is not set", var))),
+        }
+    } else if let Some(hex) = matches.value_of("key-hex") {
+        Key::from_hex(hex)
     } else {
-        let key = matches.opt_str("k").unwrap();
-        match encrypt_file(&input, &output, &key) {
-            Ok(_) => println!("Done!"),
-            Err(err) => println!("Error found: {:?}", err),
+        let key = try!(Key::from_vec(try!(rpassword::read_password_from_tty(Some("Key: ")))
+            .into_bytes()));
+        if confirm {
+            let repeated = try!(rpassword::read_password_from_tty(Some("Repeat the key: ")));
+            if Key::from_vec(repeated.into_bytes()).ok().as_ref() != Some(&key) {
+                return Err(Error::InvalidKey("The keys don't match".to_string()));
+            }
         }
+        Ok(key)
+    }
+}
 
+fn pipeline_arg() -> Arg<'static, 'static> {
+    Arg::with_name("pipeline")
+        .long("pipeline")
+        .value_name("SPEC")
+        .help("Chain ciphers instead of the XOR, as in vigenere:KEY,columnar:KEY,xor:KEY. \
+               Their keys are part of the spec")
+        .takes_value(true)
+        .validator(pipeline_spec)
+        .conflicts_with_all(&["key", "key-file", "key-env", "key-hex"])
+}
+
+fn pipeline_spec(value: String) -> Result<(), String> {
+    value.parse::<Pipeline>().map(|_| ()).map_err(|err| err.to_string())
+}
+
+fn language_arg() -> Arg<'static, 'static> {
+    Arg::with_name("language")
+        .short("l")
+        .long("language")
+        .value_name("LANGUAGE")
+        .help("Language of the plain text, used by the letter ciphers")
+        .takes_value(true)
+        .possible_values(&["english", "spanish"])
+        .default_value("english")
+}
+
+fn overwrite_arg() -> Arg<'static, 'static> {
+    Arg::with_name("overwrite")
+        .long("overwrite")
+        .help("Replace the output file if it exists, the default")
+        .conflicts_with("no-clobber")
+}
+
+fn no_clobber_arg() -> Arg<'static, 'static> {
+    Arg::with_name("no-clobber")
+        .long("no-clobber")
+        .help("Fail instead of replacing an existing output file")
+}
+
+fn positive_number(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("expected a positive number, got '{}'", value)),
     }
+}
+
+fn confidence_number(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(()),
+        _ => Err(format!("expected a number between 0 and 1, got '{}'", value)),
+    }
+}
 
+fn build_cli() -> App<'static, 'static> {
+    App::new("vigenere")
+        .version(crate_version!())
+        .about("Encrypt, decrypt and break files using the Vigenere cipher")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(SubCommand::with_name("encrypt")
+            .about("Encrypt a file with a key")
+            .arg(input_arg())
+            .arg(output_arg())
+            .args(&key_args())
+            .arg(pipeline_arg())
+            .arg(overwrite_arg())
+            .arg(no_clobber_arg()))
+        .subcommand(SubCommand::with_name("decrypt")
+            .about("Decrypt a file with a key")
+            .arg(input_arg())
+            .arg(output_arg())
+            .args(&key_args())
+            .arg(pipeline_arg())
+            .arg(overwrite_arg())
+            .arg(no_clobber_arg()))
+        .subcommand(SubCommand::with_name("break")
+            .about("Break an encrypted file without the key")
+            .arg(input_arg())
+            .arg(output_arg())
+            .arg(overwrite_arg())
+            .arg(no_clobber_arg())
+            .arg(Arg::with_name("progress")
+                .short("p")
+                .long("progress")
+                .value_name("FORMAT")
+                .help("Report progress while breaking")
+                .takes_value(true)
+                .possible_values(&["bar", "json"]))
+            .arg(Arg::with_name("report")
+                .short("r")
+                .long("report")
+                .value_name("REPORT_FILE")
+                .help("Explain the break in a report, JSON if the file name ends in .json and \
+                       Markdown otherwise")
+                .takes_value(true))
+            .arg(Arg::with_name("threads")
+                .long("threads")
+                .value_name("N")
+                .help("Number of worker threads, XOR only")
+                .takes_value(true)
+                .validator(positive_number))
+            .arg(Arg::with_name("threshold")
+                .long("threshold")
+                .value_name("CONFIDENCE")
+                .help("Stop as soon as a key reaches this confidence, between 0 and 1, XOR only")
+                .takes_value(true)
+                .validator(confidence_number))
+            .arg(Arg::with_name("cipher")
+                .short("c")
+                .long("cipher")
+                .value_name("CIPHER")
+                .help("Cipher to break, letter ciphers read the text as is instead of base64, \
+                       auto identifies it")
+                .takes_value(true)
+                .possible_values(&["xor", "vigenere", "quagmire1", "gronsfeld", "porta",
+                                   "trithemius", "auto"])
+                .default_value("xor"))
+            .arg(language_arg()))
+        .subcommand(SubCommand::with_name("analyze")
+            .about("Show the key size scores, likely cipher or statistics of an encrypted file")
+            .arg(input_arg())
+            .arg(Arg::with_name("detect-xor")
+                .short("x")
+                .long("detect-xor")
+                .help("Rank the hex lines of the input by how likely they are XOR encrypted")
+                .conflicts_with("identify"))
+            .arg(Arg::with_name("identify")
+                .long("identify")
+                .help("Rank the cipher types the input could be encrypted with, and why")
+                .conflicts_with("stats"))
+            .arg(Arg::with_name("stats")
+                .long("stats")
+                .help("Print the frequencies, index of coincidence, entropy and period scores")
+                .conflicts_with("detect-xor"))
+            .arg(Arg::with_name("json")
+                .long("json")
+                .help("Print the statistics as JSON")
+                .requires("stats"))
+            .arg(Arg::with_name("max-period")
+                .long("max-period")
+                .value_name("N")
+                .help("Longest period of the statistics")
+                .takes_value(true)
+                .default_value("20")
+                .validator(positive_number))
+            .arg(language_arg()))
+        .subcommand(SubCommand::with_name("completions")
+            .about("Generate a shell completion script on stdout")
+            .arg(Arg::with_name("shell")
+                .help("Shell to generate the script for")
+                .required(true)
+                .possible_values(&Shell::variants())))
+}
+
+const STDIO: &str = "-";
+
+fn open_input(path: &str) -> Result<Box<dyn Read>, Error> {
+    if path == STDIO {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(try!(File::open(path))))
+    }
+}
+
+/// Where the results go. Files are written atomically, they only replace the
+/// destination once everything was written.
+enum Output {
+    Stdout(io::Stdout),
+    File(AtomicFile),
+}
+
+impl Output {
+    fn open(input: &str, output: &str, policy: WritePolicy) -> Result<Output, Error> {
+        if output == STDIO {
+            Ok(Output::Stdout(io::stdout()))
+        } else {
+            if input != STDIO {
+                try!(check_distinct(input, output));
+            }
+            Ok(Output::File(try!(AtomicFile::create(output, policy))))
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush().map_err(Error::from),
+            Output::File(file) => file.commit(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Output::Stdout(ref mut stdout) => stdout.write(buf),
+            Output::File(ref mut file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Output::Stdout(ref mut stdout) => stdout.flush(),
+            Output::File(ref mut file) => file.flush(),
+        }
+    }
+}
+
+fn write_policy(matches: &ArgMatches) -> WritePolicy {
+    if matches.is_present("no-clobber") {
+        WritePolicy::NoClobber
+    } else {
+        WritePolicy::Overwrite
+    }
+}
+
+fn run_encrypt(matches: &ArgMatches) -> Result<(), Error> {
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output").unwrap();
+    if let Some(spec) = matches.value_of("pipeline") {
+        let pipeline: Pipeline = try!(spec.parse());
+        let mut output = try!(Output::open(input, output, write_policy(matches)));
+        try!(pipeline.encrypt_stream(try!(open_input(input)), &mut output));
+        return output.finish();
+    }
+    let key = try!(read_key(matches, true));
+    let mut output = try!(Output::open(input, output, write_policy(matches)));
+    try!(encrypt_stream(try!(open_input(input)), &mut output, &key));
+    output.finish()
+}
+
+fn run_decrypt(matches: &ArgMatches) -> Result<(), Error> {
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output").unwrap();
+    if let Some(spec) = matches.value_of("pipeline") {
+        let pipeline: Pipeline = try!(spec.parse());
+        let mut output = try!(Output::open(input, output, write_policy(matches)));
+        try!(pipeline.decrypt_stream(try!(open_input(input)), &mut output));
+        return output.finish();
+    }
+    let key = try!(read_key(matches, false));
+    let mut output = try!(Output::open(input, output, write_policy(matches)));
+    try!(decrypt_stream(try!(open_input(input)), &mut output, &key));
+    output.finish()
+}
+
+fn break_with(input: &str,
+              output: &str,
+              policy: WritePolicy,
+              options: &ParallelOptions,
+              progress: &mut dyn Progress)
+              -> Result<BreakReport, Error> {
+    let mut output = try!(Output::open(input, output, policy));
+    let report = try!(break_stream(try!(open_input(input)), &mut output, options, progress));
+    try!(output.finish());
+    Ok(report)
+}
+
+fn run_break(matches: &ArgMatches) -> Result<(), Error> {
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output").unwrap();
+    let policy = write_policy(matches);
+    let mut options = ParallelOptions::default();
+    if let Some(threads) = matches.value_of("threads") {
+        options.threads = threads.parse().unwrap();
+    }
+    options.threshold = matches.value_of("threshold").map(|threshold| threshold.parse().unwrap());
+    options.cipher = matches.value_of("cipher").unwrap().parse().unwrap();
+    options.language = matches.value_of("language").unwrap().parse().unwrap();
+
+    // JSON events go to stderr when stdout carries the plain text
+    let json = JsonProgress { stderr: output == STDIO };
+    let result = match matches.value_of("progress") {
+        Some("bar") => {
+            let mut bar = ProgressBar::default();
+            let result = break_with(input, output, policy, &options, &mut bar);
+            bar.finish();
+            result
+        }
+        Some("json") => break_with(input, output, policy, &options, &mut json.clone()),
+        _ => break_with(input, output, policy, &options, &mut NoProgress),
+    };
+    let result = result.and_then(|report| match matches.value_of("report") {
+        Some(path) => write_report(&report, path, policy),
+        None => Ok(()),
+    });
+
+    if matches.value_of("progress") == Some("json") {
+        match result {
+            Ok(_) => json.emit("done", vec![]),
+            Err(ref err) => json.emit("error", vec![("message", err.to_string().to_json())]),
+        }
+    }
+    result
+}
+
+fn run_analyze(matches: &ArgMatches) -> Result<(), Error> {
+    let input = matches.value_of("input").unwrap();
+    if matches.is_present("detect-xor") {
+        for detection in try!(detect_reader(try!(open_input(input)))) {
+            let text = detection.plain.to_string();
+            println!("line {:>5}  key size {:>2}  confidence {:.2}  {:?}",
+                     detection.line,
+                     detection.key_size,
+                     detection.confidence,
+                     text);
+        }
+    } else if matches.is_present("stats") {
+        let text = try!(cipher::CipherText::from_text_reader(try!(open_input(input))));
+        let bytes = identify::binary_or_text(text);
+        let max_period = matches.value_of("max-period").unwrap().parse().unwrap();
+        let language = matches.value_of("language").unwrap().parse().unwrap();
+        let analysis = Analysis::new(&bytes, max_period, language);
+        if matches.is_present("json") {
+            println!("{}", analysis.to_json());
+        } else {
+            print!("{}", analysis.to_text());
+        }
+    } else if matches.is_present("identify") {
+        let cipher = try!(cipher::CipherText::from_text_reader(try!(open_input(input))));
+        let language = matches.value_of("language").unwrap().parse().unwrap();
+        for hypothesis in identify::identify(&cipher, language) {
+            println!("{:.2}  {}", hypothesis.score, hypothesis.cipher);
+            for evidence in hypothesis.evidence {
+                println!("      {}", evidence);
+            }
+        }
+    } else {
+        let cipher = try!(cipher::CipherText::from_reader(try!(open_input(input))));
+        let language = matches.value_of("language").unwrap().parse().unwrap();
+        let guesses = breaker::guess_key_size(&cipher, language);
+        println!("{:>4}  {:>13}  {:>6}", "size", "hamming score", "ioc");
+        for size in breaker::key_size_table(&cipher, &guesses) {
+            println!("{:>4}  {:>13.4}  {:>6.4}{}",
+                     size.size,
+                     size.hamming,
+                     size.ioc,
+                     if size.guessed { "  *" } else { "" });
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let matches = build_cli().get_matches();
+
+    let result = match matches.subcommand() {
+        ("encrypt", Some(matches)) => run_encrypt(matches),
+        ("decrypt", Some(matches)) => run_decrypt(matches),
+        ("break", Some(matches)) => run_break(matches),
+        ("analyze", Some(matches)) => run_analyze(matches),
+        ("completions", Some(matches)) => {
+            let shell = value_t!(matches, "shell", Shell).unwrap_or_else(|e| e.exit());
+            build_cli().gen_completions_to("vigenere", shell, &mut io::stdout());
+            Ok(())
+        }
+        _ => unreachable!(),
+    };
+
+    if let Err(err) = result {
+        let _ = writeln!(io::stderr(), "Error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,308 @@
+use super::cipher::{self, Alphabets, CipherText, Error, Key, PlainText, Quagmire};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// One cipher of a pipeline, with its key.
+#[derive(Clone, Debug)]
+pub enum Stage {
+    Xor(Key),
+    Vigenere(Key),
+    Gronsfeld(Key),
+    Porta(Key),
+    Quagmire(Alphabets, Key),
+    /// Trithemius with the start shift, from 0 to 25.
+    Trithemius(u8),
+    Columnar(Key),
+}
+
+impl Stage {
+    fn encrypt(&self, text: PlainText) -> Result<CipherText, Error> {
+        match *self {
+            Stage::Xor(ref key) => Ok(cipher::encrypt_in_place(text, key)),
+            Stage::Vigenere(ref key) => cipher::encrypt_vigenere(&text, key),
+            Stage::Gronsfeld(ref key) => cipher::encrypt_gronsfeld(&text, key),
+            Stage::Porta(ref key) => cipher::encrypt_porta(&text, key),
+            Stage::Quagmire(ref alphabets, ref key) => {
+                cipher::encrypt_quagmire(&text, alphabets, key)
+            }
+            Stage::Trithemius(start) => Ok(cipher::encrypt_trithemius(&text, start)),
+            Stage::Columnar(ref key) => Ok(cipher::encrypt_columnar(&text, key)),
+        }
+    }
+
+    fn decrypt(&self, cipher_text: CipherText) -> Result<PlainText, Error> {
+        match *self {
+            Stage::Xor(ref key) => Ok(cipher::decrypt_in_place(cipher_text, key)),
+            Stage::Vigenere(ref key) => cipher::decrypt_vigenere(&cipher_text, key),
+            Stage::Gronsfeld(ref key) => cipher::decrypt_gronsfeld(&cipher_text, key),
+            Stage::Porta(ref key) => cipher::decrypt_porta(&cipher_text, key),
+            Stage::Quagmire(ref alphabets, ref key) => {
+                cipher::decrypt_quagmire(&cipher_text, alphabets, key)
+            }
+            Stage::Trithemius(start) => Ok(cipher::decrypt_trithemius(&cipher_text, start)),
+            Stage::Columnar(ref key) => Ok(cipher::decrypt_columnar(&cipher_text, key)),
+        }
+    }
+
+    /// Parses a stage of a pipeline spec, see `Pipeline::from_str`.
+    fn parse(spec: &str) -> Result<Stage, Error> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let key = |i: usize| match parts.get(i) {
+            Some(key) => Key::from_string(key),
+            None => Err(Error::InvalidKey(format!("The stage {} needs a key", spec))),
+        };
+        let alphabets = |variant: Quagmire, cipher_keyword: &str| {
+            Alphabets::quagmire(variant, parts.get(1).cloned().unwrap_or(""), cipher_keyword)
+        };
+        let args = match parts[0] {
+            "quagmire1" | "quagmire2" | "quagmire3" => 2,
+            "quagmire4" => 3,
+            _ => 1,
+        };
+        if parts.len() != args + 1 {
+            return Err(Error::InvalidKey(format!("Wrong number of arguments in {}", spec)));
+        }
+        Ok(match parts[0] {
+            "xor" => Stage::Xor(try!(key(1))),
+            "xor-hex" => Stage::Xor(try!(Key::from_hex(parts[1]))),
+            "vigenere" => Stage::Vigenere(try!(key(1))),
+            "gronsfeld" => Stage::Gronsfeld(try!(key(1))),
+            "porta" => Stage::Porta(try!(key(1))),
+            "quagmire1" => Stage::Quagmire(try!(alphabets(Quagmire::I, "")), try!(key(2))),
+            "quagmire2" => Stage::Quagmire(try!(alphabets(Quagmire::II, "")), try!(key(2))),
+            "quagmire3" => Stage::Quagmire(try!(alphabets(Quagmire::III, "")), try!(key(2))),
+            "quagmire4" => {
+                Stage::Quagmire(try!(alphabets(Quagmire::IV, parts[2])), try!(key(3)))
+            }
+            "trithemius" => {
+                match parts[1].bytes().next().and_then(cipher::letter_index) {
+                    Some(start) if parts[1].len() == 1 => Stage::Trithemius(start),
+                    _ => return Err(Error::InvalidKey("The start must be a letter".to_string())),
+                }
+            }
+            "columnar" => Stage::Columnar(try!(key(1))),
+            name => return Err(Error::InvalidKey(format!("Unknown cipher {}", name))),
+        })
+    }
+}
+
+/// Ciphers applied one after the other, a product cipher. Decrypting runs the
+/// stages in reverse order.
+///
+/// ```
+/// use vigenere::cipher::{Key, PlainText};
+/// use vigenere::pipeline::Pipeline;
+///
+/// let pipeline = Pipeline::new()
+///     .vigenere(Key::from_string("lemon").unwrap())
+///     .columnar(Key::from_string("zebras").unwrap());
+/// let cipher = pipeline.encrypt(PlainText::from_string("attack at dawn")).unwrap();
+/// let plain = pipeline.decrypt(cipher).unwrap();
+/// assert_eq!("attack at dawn", plain.to_string());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    pub fn stage(mut self, stage: Stage) -> Pipeline {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn xor(self, key: Key) -> Pipeline {
+        self.stage(Stage::Xor(key))
+    }
+
+    pub fn vigenere(self, key: Key) -> Pipeline {
+        self.stage(Stage::Vigenere(key))
+    }
+
+    pub fn gronsfeld(self, key: Key) -> Pipeline {
+        self.stage(Stage::Gronsfeld(key))
+    }
+
+    pub fn porta(self, key: Key) -> Pipeline {
+        self.stage(Stage::Porta(key))
+    }
+
+    pub fn quagmire(self, alphabets: Alphabets, key: Key) -> Pipeline {
+        self.stage(Stage::Quagmire(alphabets, key))
+    }
+
+    pub fn trithemius(self, start: u8) -> Pipeline {
+        self.stage(Stage::Trithemius(start))
+    }
+
+    pub fn columnar(self, key: Key) -> Pipeline {
+        self.stage(Stage::Columnar(key))
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Whether the cipher text is binary, and read and written base64 encoded.
+    /// It is as soon as a stage is XOR.
+    pub fn is_binary(&self) -> bool {
+        self.stages.iter().any(|stage| matches!(*stage, Stage::Xor(_)))
+    }
+
+    pub fn encrypt(&self, text: PlainText) -> Result<CipherText, Error> {
+        let mut bytes = Vec::from(text);
+        for stage in &self.stages {
+            bytes = Vec::from(try!(stage.encrypt(PlainText::from(bytes))));
+        }
+        Ok(CipherText::from(bytes))
+    }
+
+    pub fn decrypt(&self, cipher_text: CipherText) -> Result<PlainText, Error> {
+        let mut bytes = Vec::from(cipher_text);
+        for stage in self.stages.iter().rev() {
+            bytes = Vec::from(try!(stage.decrypt(CipherText::from(bytes))));
+        }
+        Ok(PlainText::from(bytes))
+    }
+
+    /// Reads the plain text from `input` and writes the cipher text to `output`,
+    /// base64 encoded if the pipeline `is_binary`.
+    pub fn encrypt_stream<R: Read, W: Write>(&self, input: R, output: W) -> Result<(), Error> {
+        let cipher_text = try!(self.encrypt(try!(PlainText::from_reader(input))));
+        if self.is_binary() {
+            cipher_text.to_writer(output)
+        } else {
+            cipher_text.to_text_writer(output)
+        }
+    }
+
+    /// Reads the cipher text from `input`, base64 encoded if the pipeline
+    /// `is_binary`, and writes the plain text to `output`.
+    pub fn decrypt_stream<R: Read, W: Write>(&self, input: R, output: W) -> Result<(), Error> {
+        let cipher_text = if self.is_binary() {
+            try!(CipherText::from_reader(input))
+        } else {
+            try!(CipherText::from_text_reader(input))
+        };
+        let mut plain = try!(self.decrypt(cipher_text));
+        plain.set_wipe_on_drop(true);
+        plain.to_writer(output)
+    }
+}
+
+/// Parses a spec with the stages separated by commas, each one the cipher name
+/// and its arguments separated by colons:
+///
+/// - `xor:KEY`, or `xor-hex:HEX` for binary keys
+/// - `vigenere:KEY`, `gronsfeld:DIGITS`, `porta:KEY` and `columnar:KEY`
+/// - `quagmire1:KEYWORD:KEY` to `quagmire3:KEYWORD:KEY`, and
+///   `quagmire4:KEYWORD:CIPHER_KEYWORD:KEY`
+/// - `trithemius:START`, the start shift as a letter
+///
+/// For example `vigenere:lemon,columnar:zebras,xor:secret`.
+impl FromStr for Pipeline {
+    type Err = Error;
+
+    fn from_str(spec: &str) -> Result<Pipeline, Error> {
+        if spec.is_empty() {
+            return Err(Error::InvalidKey("The pipeline has no stages".to_string()));
+        }
+        let mut pipeline = Pipeline::new();
+        for stage in spec.split(',') {
+            pipeline = pipeline.stage(try!(Stage::parse(stage)));
+        }
+        Ok(pipeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::cipher::{self, Alphabets, Key, PlainText, Quagmire};
+
+    const TEXT: &str = "Attack at dawn! 日本語";
+
+    fn to_key(key: &str) -> Key {
+        Key::from_string(key).unwrap()
+    }
+
+    #[test]
+    fn test_builder() {
+        let pipeline = Pipeline::new().vigenere(to_key("lemon")).columnar(to_key("zebras"));
+        let vigenere = cipher::encrypt_vigenere(&PlainText::from_string(TEXT), &to_key("lemon"))
+            .unwrap();
+        let expected = cipher::encrypt_columnar(&PlainText::from(Vec::from(vigenere)),
+                                                &to_key("zebras"));
+        assert_eq!(expected,
+                   pipeline.encrypt(PlainText::from_string(TEXT)).unwrap());
+        check_round_trip(&pipeline);
+        assert!(!pipeline.is_binary());
+    }
+
+    #[test]
+    fn test_reverse_order() {
+        // Double transposition is two columnar stages, undone last to first
+        let pipeline = Pipeline::new().columnar(to_key("zebras")).columnar(to_key("lemon"));
+        let cipher_text = pipeline.encrypt(PlainText::from_string(TEXT)).unwrap();
+        assert_eq!(cipher::encrypt_double_columnar(&PlainText::from_string(TEXT),
+                                                   &to_key("zebras"),
+                                                   &to_key("lemon")),
+                   cipher_text);
+        check_round_trip(&pipeline);
+    }
+
+    #[test]
+    fn test_every_stage() {
+        let alphabets = Alphabets::quagmire(Quagmire::IV, "highway", "automobile").unwrap();
+        let pipeline = Pipeline::new()
+            .vigenere(to_key("lemon"))
+            .gronsfeld(to_key("31415"))
+            .porta(to_key("fortification"))
+            .quagmire(alphabets, to_key("key"))
+            .trithemius(3)
+            .columnar(to_key("zebras"))
+            .xor(to_key("secret"));
+        assert!(pipeline.is_binary());
+        check_round_trip(&pipeline);
+        check_round_trip(&Pipeline::new());
+    }
+
+    #[test]
+    fn test_spec() {
+        let pipeline: Pipeline = "vigenere:lemon,columnar:zebras,xor-hex:00ff".parse().unwrap();
+        assert_eq!(3, pipeline.stages().len());
+        assert!(pipeline.is_binary());
+        check_round_trip(&pipeline);
+        let pipeline: Pipeline = "quagmire4:highway:automobile:key,trithemius:d,porta:ab"
+            .parse()
+            .unwrap();
+        check_round_trip(&pipeline);
+
+        for spec in &["", "enigma:key", "vigenere", "vigenere:", "quagmire1:key", "trithemius:3",
+                      "trithemius:", "vigenere:lemon,"] {
+            match spec.parse::<Pipeline>() {
+                Err(Error::InvalidKey(_)) => {}
+                _ => panic!("{:?} isn't a valid pipeline", spec),
+            }
+        }
+    }
+
+    #[test]
+    fn test_streams() {
+        let pipeline: Pipeline = "vigenere:lemon,columnar:zebras".parse().unwrap();
+        let mut cipher_text = Vec::new();
+        pipeline.encrypt_stream(TEXT.as_bytes(), &mut cipher_text).unwrap();
+        let mut plain = Vec::new();
+        pipeline.decrypt_stream(&cipher_text[..], &mut plain).unwrap();
+        assert_eq!(TEXT.as_bytes().to_vec(), plain);
+    }
+
+    fn check_round_trip(pipeline: &Pipeline) {
+        let cipher_text = pipeline.encrypt(PlainText::from_string(TEXT)).unwrap();
+        assert_eq!(TEXT, pipeline.decrypt(cipher_text).unwrap().to_string());
+    }
+}
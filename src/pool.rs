@@ -0,0 +1,93 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Fixed size pool of worker threads pulling jobs from a shared queue.
+/// Dropping the pool waits for the queued jobs to finish.
+pub struct ThreadPool {
+    workers: Vec<thread::JoinHandle<()>>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> ThreadPool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut workers = Vec::with_capacity(size);
+        for _ in 0..size.max(1) {
+            let receiver = receiver.clone();
+            workers.push(thread::spawn(move || loop {
+                let job = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => break,
+                };
+                match job {
+                    // A panicking job must not take the worker down with it, the
+                    // job owner notices through its dropped result channel.
+                    Ok(job) => {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                    Err(_) => break,
+                }
+            }));
+        }
+
+        ThreadPool {
+            workers: workers,
+            sender: Some(sender),
+        }
+    }
+
+    pub fn execute<F>(&self, job: F)
+        where F: FnOnce() + Send + 'static
+    {
+        if let Some(ref sender) = self.sender {
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_jobs() {
+        let (tx, rx) = mpsc::channel();
+        {
+            let pool = ThreadPool::new(3);
+            for i in 0..20 {
+                let tx = tx.clone();
+                pool.execute(move || tx.send(i).unwrap());
+            }
+        }
+        drop(tx);
+        let mut results: Vec<i32> = rx.iter().collect();
+        results.sort();
+        assert_eq!((0..20).collect::<Vec<i32>>(), results);
+    }
+
+    #[test]
+    fn test_survive_panic() {
+        let (tx, rx) = mpsc::channel();
+        {
+            let pool = ThreadPool::new(1);
+            pool.execute(|| panic!("worker failure"));
+            let tx = tx.clone();
+            pool.execute(move || tx.send(42).unwrap());
+        }
+        drop(tx);
+        assert_eq!(vec![42], rx.iter().collect::<Vec<i32>>());
+    }
+}
@@ -0,0 +1,223 @@
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Scores of one key size: the normalized Hamming distance used by
+/// `guess_key_size` (lower is better), the average index of coincidence of its
+/// columns (higher is better), and whether it was one of the guesses tried.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeySizeScore {
+    pub size: u32,
+    pub hamming: f32,
+    pub ioc: f32,
+    pub guessed: bool,
+}
+
+/// Coincidences of a text with itself shifted by `offset`: how many positions
+/// hold the same byte as the one `offset` later, and their fraction of the
+/// positions compared.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Coincidence {
+    pub offset: u32,
+    pub count: u32,
+    pub rate: f32,
+}
+
+/// Key byte chosen for one column, with its score and the best score among the
+/// other keys. For XOR the score counts the bytes that look like text; for the
+/// letter ciphers the key is a letter and the score is the chi-squared statistic,
+/// so lower is better.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColumnKey {
+    pub key: u8,
+    pub score: u32,
+    pub runner_up: u8,
+    pub runner_up_score: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct BreakReport {
+    pub key_sizes: Vec<KeySizeScore>,
+    pub key_size: u32,
+    pub columns: Vec<ColumnKey>,
+    pub confidence: f32,
+    pub elapsed: Duration,
+}
+
+impl BreakReport {
+    pub fn key(&self) -> Vec<u8> {
+        self.columns.iter().map(|column| column.key).collect()
+    }
+
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed.as_secs() as f64 * 1000.0 + self.elapsed.subsec_nanos() as f64 / 1_000_000.0
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let key = self.key();
+        let _ = writeln!(out, "# Break report\n");
+        let _ = writeln!(out, "- Key size: {}", self.key_size);
+        match printable(&key) {
+            Some(text) => {
+                let _ = writeln!(out, "- Key: `{}` (hex `{}`)", text, key.to_hex());
+            }
+            None => {
+                let _ = writeln!(out, "- Key: hex `{}`", key.to_hex());
+            }
+        }
+        let _ = writeln!(out, "- Plain text confidence: {:.3}", self.confidence);
+        let _ = writeln!(out, "- Time: {:.1} ms\n", self.elapsed_ms());
+
+        let _ = writeln!(out, "## Key sizes\n");
+        let _ = writeln!(out, "| Size | Hamming score | IoC | Tried |");
+        let _ = writeln!(out, "|-----:|--------------:|----:|:-----:|");
+        for size in self.key_sizes.iter() {
+            let _ = writeln!(out,
+                             "| {} | {:.4} | {:.4} | {} |",
+                             size.size,
+                             size.hamming,
+                             size.ioc,
+                             if size.guessed { "yes" } else { "" });
+        }
+
+        let _ = writeln!(out, "\n## Columns\n");
+        let _ = writeln!(out, "| Column | Key | Score | Runner-up | Runner-up score |");
+        let _ = writeln!(out, "|-------:|----:|------:|----------:|----------------:|");
+        for (i, column) in self.columns.iter().enumerate() {
+            let _ = writeln!(out,
+                             "| {} | {} | {} | {} | {} |",
+                             i,
+                             byte_label(column.key),
+                             column.score,
+                             byte_label(column.runner_up),
+                             column.runner_up_score);
+        }
+        out
+    }
+}
+
+impl ToJson for KeySizeScore {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("size".to_string(), self.size.to_json());
+        object.insert("hamming".to_string(), self.hamming.to_json());
+        object.insert("ioc".to_string(), self.ioc.to_json());
+        object.insert("guessed".to_string(), self.guessed.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for Coincidence {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("offset".to_string(), self.offset.to_json());
+        object.insert("count".to_string(), self.count.to_json());
+        object.insert("rate".to_string(), self.rate.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for ColumnKey {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("key".to_string(), self.key.to_json());
+        object.insert("score".to_string(), self.score.to_json());
+        object.insert("runner_up".to_string(), self.runner_up.to_json());
+        object.insert("runner_up_score".to_string(), self.runner_up_score.to_json());
+        Json::Object(object)
+    }
+}
+
+impl ToJson for BreakReport {
+    fn to_json(&self) -> Json {
+        let key = self.key();
+        let mut object = BTreeMap::new();
+        object.insert("key_size".to_string(), self.key_size.to_json());
+        object.insert("key_hex".to_string(), key.to_hex().to_json());
+        if let Some(text) = printable(&key) {
+            object.insert("key".to_string(), text.to_json());
+        }
+        object.insert("confidence".to_string(), self.confidence.to_json());
+        object.insert("elapsed_ms".to_string(), self.elapsed_ms().to_json());
+        object.insert("key_sizes".to_string(), self.key_sizes.to_json());
+        object.insert("columns".to_string(), self.columns.to_json());
+        Json::Object(object)
+    }
+}
+
+fn printable(bytes: &[u8]) -> Option<String> {
+    if bytes.iter().all(|b| *b >= 0x20 && *b < 0x7f) {
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    } else {
+        None
+    }
+}
+
+fn byte_label(byte: u8) -> String {
+    if byte > 0x20 && byte < 0x7f && byte != b'|' && byte != b'`' {
+        format!("`{}` (0x{:02x})", byte as char, byte)
+    } else {
+        format!("0x{:02x}", byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::{Json, ToJson};
+    use std::time::Duration;
+
+    fn report() -> BreakReport {
+        BreakReport {
+            key_sizes: vec![KeySizeScore {
+                                size: 1,
+                                hamming: 3.5,
+                                ioc: 0.04,
+                                guessed: false,
+                            },
+                            KeySizeScore {
+                                size: 2,
+                                hamming: 2.0,
+                                ioc: 0.07,
+                                guessed: true,
+                            }],
+            key_size: 2,
+            columns: vec![ColumnKey {
+                              key: b'o',
+                              score: 40,
+                              runner_up: b'O',
+                              runner_up_score: 12,
+                          },
+                          ColumnKey {
+                              key: b'k',
+                              score: 38,
+                              runner_up: 0,
+                              runner_up_score: 10,
+                          }],
+            confidence: 0.78,
+            elapsed: Duration::from_millis(12),
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let json = report().to_json();
+        let parsed = Json::from_str(&json.to_string()).unwrap();
+        assert_eq!(Some("ok"), parsed.find("key").and_then(Json::as_string));
+        assert_eq!(Some("6f6b"), parsed.find("key_hex").and_then(Json::as_string));
+        assert_eq!(Some(2), parsed.find("key_size").and_then(Json::as_u64));
+        assert_eq!(Some(12.0), parsed.find("elapsed_ms").and_then(Json::as_f64));
+        assert_eq!(2, parsed.find("columns").and_then(Json::as_array).unwrap().len());
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = report().to_markdown();
+        assert!(markdown.contains("- Key: `ok` (hex `6f6b`)"));
+        assert!(markdown.contains("| 2 | 2.0000 | 0.0700 | yes |"));
+        assert!(markdown.contains("| 1 | `k` (0x6b) | 38 | 0x00 | 10 |"));
+    }
+}